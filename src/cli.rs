@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use eigen_trust_algorithm::commitment::{hash_inputs, Commit, ScoreTree};
+use eigen_trust_algorithm::config::Config;
+use eigen_trust_algorithm::eigen_trust::{EigenTrustConfig, EigenTrustError, OverlapPolicy};
+use eigen_trust_algorithm::fixed_point::FixedPointError;
+use eigen_trust_algorithm::io::{
+    load_trust, read_edges, read_values, save_scores, to_vector, write_scores, PeerIndex,
+    TrustInput,
+};
+use eigen_trust_algorithm::linalg::Scalar;
+use eigen_trust_algorithm::sparse::CsrMatrix;
+use eigen_trust_algorithm::transitive_trust::{self, Graph};
+use eigen_trust_algorithm::utils::Execution;
+use eigen_trust_algorithm::{eigen_trust, fixed_point, gnn, hubs_and_auth, page_rank};
+
+const USAGE: &str = "\
+Usage: eigen-trust-algorithm <command> [options]
+
+Commands:
+  eigentrust  --trust <csv> --pre-trust <csv> [--distrust <csv>]
+              [--trust-output <csv>] [--distrust-output <csv>]
+              [--sanitise net|keep-larger|drop-both] [--precision f32|f64|fixed]
+              [--commitment <json>] [--prove <peer> --proof <json>]
+              [--example functional|sybil|sleeping-agent]
+  pagerank    --graph <csv> --pre-trust <csv> [--seed <csv>]
+              [--precision f32|f64] [--example]
+  hits        --graph <csv> [--hubs <csv>] [--auth <csv>] [--example]
+  transitive  --graph <csv> --source <peer> [--example]
+  gnn-train   --graph <csv> --labels <csv> [--biases <csv>]
+              [--runs <n>] [--steps <n>] [--example]
+
+Common options:
+  --config <file>  algorithm parameters as TOML, or JSON for a `.json` file
+  --output <csv>   write `peer,score` rows to a file instead of stdout
+
+Graphs are `from,to,value` rows and vectors are `peer,value` rows, where peers
+can be any identifier. Negative values in a trust graph are read as distrust,
+so EigenTrust and transitive trust both accept a single signed edge list.";
+
+type Options = HashMap<String, String>;
+
+pub fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let Some((command, rest)) = args.split_first() else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    let options = parse_options(rest)?;
+
+    type Command = fn(&Options) -> Result<(), Box<dyn Error>>;
+    let (handler, keys): (Command, &[&str]) = match command.as_str() {
+        "eigentrust" => (eigentrust, EIGENTRUST_OPTIONS),
+        "pagerank" => (pagerank, PAGERANK_OPTIONS),
+        "hits" => (hits, HITS_OPTIONS),
+        "transitive" => (transitive, TRANSITIVE_OPTIONS),
+        "gnn-train" => (gnn_train, GNN_OPTIONS),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        _ => return Err(format!("unknown command `{}`\n\n{}", command, USAGE).into()),
+    };
+    check_options(command, &options, keys)?;
+    handler(&options)
+}
+
+// Options each command reads, on top of `COMMON_OPTIONS`
+const COMMON_OPTIONS: &[&str] = &["config", "output"];
+const EIGENTRUST_OPTIONS: &[&str] = &[
+    "trust",
+    "pre-trust",
+    "distrust",
+    "trust-output",
+    "distrust-output",
+    "sanitise",
+    "precision",
+    "commitment",
+    "prove",
+    "proof",
+    "example",
+];
+const PAGERANK_OPTIONS: &[&str] = &["graph", "pre-trust", "seed", "precision", "example"];
+const HITS_OPTIONS: &[&str] = &["graph", "hubs", "auth", "example"];
+const TRANSITIVE_OPTIONS: &[&str] = &["graph", "source", "example"];
+const GNN_OPTIONS: &[&str] = &["graph", "labels", "biases", "runs", "steps", "example"];
+
+// A misspelt option would otherwise be ignored and the command run without it
+fn check_options(command: &str, options: &Options, keys: &[&str]) -> Result<(), Box<dyn Error>> {
+    let mut unknown: Vec<&str> = options
+        .keys()
+        .map(|key| key.as_str())
+        .filter(|key| !keys.contains(key) && !COMMON_OPTIONS.contains(key))
+        .collect();
+    unknown.sort_unstable();
+    match unknown.first() {
+        Some(key) => {
+            Err(format!("unknown option `--{}` for `{}`\n\n{}", key, command, USAGE).into())
+        }
+        None => Ok(()),
+    }
+}
+
+// Parse `--key value` pairs - a key without a value is a flag
+fn parse_options(args: &[String]) -> Result<Options, Box<dyn Error>> {
+    let mut options = Options::new();
+    let mut i = 0;
+    while i < args.len() {
+        let Some(key) = args[i].strip_prefix("--") else {
+            return Err(format!("unexpected argument `{}`", args[i]).into());
+        };
+        match args.get(i + 1) {
+            Some(value) if !value.starts_with("--") => {
+                options.insert(key.to_string(), value.clone());
+                i += 2;
+            }
+            _ => {
+                options.insert(key.to_string(), "true".to_string());
+                i += 1;
+            }
+        }
+    }
+    Ok(options)
+}
+
+fn required<'a>(options: &'a Options, key: &str) -> Result<&'a str, Box<dyn Error>> {
+    options
+        .get(key)
+        .map(|x| x.as_str())
+        .ok_or_else(|| format!("missing required option `--{}`", key).into())
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, Box<dyn Error>> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value `{}` for `{}`", value, key).into())
+}
+
+fn load_config(options: &Options) -> Result<Config, Box<dyn Error>> {
+    match options.get("config") {
+        Some(path) => Ok(Config::from_file(Path::new(path))?),
+        None => Ok(Config::default()),
+    }
+}
+
+fn load_optional_values<T: FromStr>(
+    options: &Options,
+    key: &str,
+) -> Result<Vec<(String, T)>, Box<dyn Error>> {
+    match options.get(key) {
+        Some(path) => Ok(read_values(Path::new(path))?),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn output<T: fmt::Display>(
+    options: &Options,
+    peers: &PeerIndex,
+    scores: &[T],
+) -> Result<(), Box<dyn Error>> {
+    match options.get("output") {
+        Some(path) => save_scores(Path::new(path), peers, scores)?,
+        None => write_scores(&mut io::stdout().lock(), peers, scores)?,
+    }
+    Ok(())
+}
+
+fn eigentrust(options: &Options) -> Result<(), Box<dyn Error>> {
+    if let Some(example) = options.get("example") {
+        match example.as_str() {
+            "functional" => eigen_trust::functional_case(),
+            "sybil" => eigen_trust::sybil_case(),
+            "sleeping-agent" => eigen_trust::sleeping_agent_case(),
+            _ => return Err(format!("unknown example `{}`", example).into()),
+        }
+        return Ok(());
+    }
+
+    let config = load_config(options)?;
+    match precision(options)? {
+        Precision::F32 => eigentrust_scores::<f32>(options, &load_input(options)?, &config),
+        Precision::F64 => eigentrust_scores::<f64>(options, &load_input(options)?, &config),
+        Precision::Fixed => eigentrust_fixed_scores(options, &load_input(options)?, &config),
+    }
+}
+
+// Inputs are parsed in the precision of the run, and fixed point rounds from `f64`
+fn load_input<T: Scalar + FromStr>(options: &Options) -> Result<TrustInput<T>, Box<dyn Error>> {
+    let mut input = load_trust(
+        Path::new(required(options, "trust")?),
+        options.get("distrust").map(Path::new),
+        Path::new(required(options, "pre-trust")?),
+    )?;
+    if let Some(policy) = options.get("sanitise") {
+        let policy = match policy.as_str() {
+            "net" => OverlapPolicy::Net,
+            "keep-larger" => OverlapPolicy::KeepLarger,
+            "drop-both" => OverlapPolicy::DropBoth,
+            _ => return Err(format!("unknown overlap policy `{}`", policy).into()),
+        };
+        let (lt, ld, report) = eigen_trust::sanitise(&input.lt, &input.ld, policy);
+        for repair in &report.repairs {
+            eprintln!("repaired {}", repair.describe(|i| input.peers.id(i)));
+        }
+        input.lt = lt;
+        input.ld = ld;
+    }
+    Ok(input)
+}
+
+enum Precision {
+    F32,
+    F64,
+    Fixed,
+}
+
+fn precision(options: &Options) -> Result<Precision, Box<dyn Error>> {
+    match options.get("precision").map(|x| x.as_str()) {
+        None | Some("f32") => Ok(Precision::F32),
+        Some("f64") => Ok(Precision::F64),
+        Some("fixed") => Ok(Precision::Fixed),
+        Some(x) => Err(format!("unknown precision `{}`", x).into()),
+    }
+}
+
+fn eigentrust_scores<T: Scalar + Commit>(
+    options: &Options,
+    input: &TrustInput<T>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let scores = eigen_trust::run(&input.lt, &input.ld, &input.pre_trust, &config.eigen_trust)
+        .map_err(|e| match e {
+            EigenTrustError::Invalid(e) => e.describe(|i| input.peers.id(i)),
+            e => e.to_string(),
+        })?;
+    eprintln!("{}", scores.report);
+    if let Some(report) = scores.distrust_report {
+        eprintln!("distrust: {}", report);
+    }
+    eprintln!("adjustment: {}", scores.adjustment);
+    if let Some(path) = options.get("trust-output") {
+        save_scores(Path::new(path), &input.peers, &scores.trust)?;
+    }
+    if let Some(path) = options.get("distrust-output") {
+        save_scores(Path::new(path), &input.peers, &scores.distrust)?;
+    }
+    // Execution changes how the scores are computed, never their value, so it isn't committed
+    let params = EigenTrustConfig {
+        execution: Execution::default(),
+        ..config.eigen_trust.clone()
+    };
+    save_commitment(options, input, &params, &scores.adjusted)?;
+    output(options, &input.peers, &scores.adjusted)
+}
+
+// Scores are written as integers in units of `1 / scale`, exactly as computed
+fn eigentrust_fixed_scores(
+    options: &Options,
+    input: &TrustInput<f64>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let scale = config.fixed_point.scale;
+    let (lt, ld) = fixed_point::to_fixed_matrices(&input.lt, &input.ld, scale)
+        .map_err(|e| e.describe(|i| input.peers.id(i)))?;
+    let describe = |e| match e {
+        FixedPointError::Invalid(e) => e.describe(|i| input.peers.id(i)),
+        e => e.to_string(),
+    };
+    let pre_trust = fixed_point::to_fixed_pre_trust(&input.pre_trust, scale).map_err(describe)?;
+    let scores = fixed_point::run(&lt, &ld, &pre_trust, &config.fixed_point).map_err(describe)?;
+    eprintln!("{}", scores.report);
+    if let Some(path) = options.get("trust-output") {
+        save_scores(Path::new(path), &input.peers, &scores.trust)?;
+    }
+    if let Some(path) = options.get("distrust-output") {
+        save_scores(Path::new(path), &input.peers, &scores.distrust)?;
+    }
+    save_commitment(options, input, &config.fixed_point, &scores.adjusted)?;
+    output(options, &input.peers, &scores.adjusted)
+}
+
+// Merkle root over the adjusted scores, together with the hash of the inputs and parameters,
+// and optionally the inclusion proof of one peer's score against that root
+fn save_commitment<T: Commit, U: Scalar + Commit, C: serde::Serialize>(
+    options: &Options,
+    input: &TrustInput<U>,
+    params: &C,
+    scores: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let prove = match (options.get("prove"), options.get("proof")) {
+        (Some(peer), Some(path)) => Some((peer, path)),
+        (None, None) => None,
+        _ => return Err("`--prove` and `--proof` must be given together".into()),
+    };
+    let path = options.get("commitment");
+    if path.is_none() && prove.is_none() {
+        return Ok(());
+    }
+    let peers = input.peers.ids();
+    let tree = ScoreTree::new(peers, scores)?;
+    if let Some(path) = path {
+        let inputs = hash_inputs(peers, &input.lt, &input.ld, &input.pre_trust, params);
+        fs::write(
+            path,
+            serde_json::to_string_pretty(&tree.commitment(inputs))?,
+        )?;
+    }
+    if let Some((peer, path)) = prove {
+        let proof = tree
+            .prove(peer)
+            .ok_or_else(|| format!("unknown peer `{}`", peer))?;
+        fs::write(path, serde_json::to_string_pretty(&proof)?)?;
+    }
+    Ok(())
+}
+
+fn pagerank(options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.contains_key("example") {
+        page_rank::run_job();
+        return Ok(());
+    }
+
+    let config = load_config(options)?;
+    match precision(options)? {
+        Precision::F32 => pagerank_scores::<f32>(options, &config),
+        Precision::F64 => pagerank_scores::<f64>(options, &config),
+        Precision::Fixed => Err("fixed-point precision is only available for eigentrust".into()),
+    }
+}
+
+fn pagerank_scores<T: Scalar + FromStr>(
+    options: &Options,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut peers = PeerIndex::new();
+    let graph = peers.index_edges(&read_edges::<T>(Path::new(required(options, "graph")?))?);
+    let pre_trust = peers.index_values(&read_values::<T>(Path::new(required(
+        options,
+        "pre-trust",
+    )?))?);
+    let seed = peers.index_values(&load_optional_values::<T>(options, "seed")?);
+
+    let size = peers.len();
+    let am = CsrMatrix::from_triplets(size, graph);
+    let pre_trust = to_vector(&pre_trust, size, T::zero());
+    let seed = if seed.is_empty() {
+        pre_trust.clone()
+    } else {
+        to_vector(&seed, size, T::zero())
+    };
+
+    let (scores, report) = page_rank::run(&am, &pre_trust, &seed, &config.page_rank)?;
+    eprintln!("{}", report);
+    output(options, &peers, &scores)
+}
+
+fn hits(options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.contains_key("example") {
+        hubs_and_auth::run_job();
+        return Ok(());
+    }
+
+    let config = load_config(options)?;
+    let mut peers = PeerIndex::new();
+    let graph = peers.index_edges(&read_edges::<f32>(Path::new(required(options, "graph")?))?);
+    let hubs = peers.index_values(&load_optional_values::<f32>(options, "hubs")?);
+    let auth = peers.index_values(&load_optional_values::<f32>(options, "auth")?);
+
+    let size = peers.len();
+    let mut am = vec![vec![0.; size]; size];
+    for (i, j, value) in graph {
+        am[i][j] += value;
+    }
+    // Without an initial state every node starts out equal
+    let default = if hubs.is_empty() { 1. } else { 0. };
+    let hubs = to_vector(&hubs, size, default);
+    let default = if auth.is_empty() { 1. } else { 0. };
+    let auth = to_vector(&auth, size, default);
+
+    let (s_hubs, s_auth, report) = hubs_and_auth::run(&am, &hubs, &auth, &config.hits);
+    eprintln!("{}", report);
+    match options.get("output") {
+        Some(path) => write_hits(&mut File::create(path)?, &peers, &s_hubs, &s_auth)?,
+        None => write_hits(&mut io::stdout().lock(), &peers, &s_hubs, &s_auth)?,
+    }
+    Ok(())
+}
+
+fn write_hits<W: Write>(
+    writer: &mut W,
+    peers: &PeerIndex,
+    hubs: &[f32],
+    auth: &[f32],
+) -> io::Result<()> {
+    writeln!(writer, "peer,hub,authority")?;
+    for i in 0..hubs.len() {
+        writeln!(writer, "{},{},{}", peers.id(i), hubs[i], auth[i])?;
+    }
+    Ok(())
+}
+
+fn transitive(options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.contains_key("example") {
+        transitive_trust::run_job();
+        return Ok(());
+    }
+
+    let source = required(options, "source")?.to_string();
+    let mut graph = Graph::new();
+    for (from, to, value) in read_edges::<f32>(Path::new(required(options, "graph")?))? {
+        if value < 0. {
+            graph.add_negative_edge(from, to, -value);
+        } else {
+            graph.add_positive_edge(from, to, value);
+        }
+    }
+    let scores = transitive_trust::compute_scores(graph, source);
+
+    let mut writer: Box<dyn Write> = match options.get("output") {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    writeln!(writer, "peer,positive,negative,net")?;
+    for score in scores {
+        writeln!(
+            writer,
+            "{},{},{},{}",
+            score.node,
+            score.p_score,
+            score.n_score,
+            score.net_score()
+        )?;
+    }
+    Ok(())
+}
+
+fn gnn_train(options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.contains_key("example") {
+        gnn::run_job();
+        return Ok(());
+    }
+
+    let mut peers = PeerIndex::new();
+    let graph = peers.index_edges(&read_edges::<f32>(Path::new(required(options, "graph")?))?);
+    let labels = peers.index_values(&read_values::<f32>(Path::new(required(
+        options, "labels",
+    )?))?);
+    let biases = peers.index_values(&load_optional_values::<f32>(options, "biases")?);
+    let runs = match options.get("runs") {
+        Some(x) => parse_number("runs", x)?,
+        None => 100,
+    };
+    let steps = match options.get("steps") {
+        Some(x) => parse_number("steps", x)?,
+        None => 100,
+    };
+
+    let size = peers.len();
+    let mut edges = vec![vec![false; size]; size];
+    for (i, j, value) in graph {
+        edges[i][j] = value != 0.;
+    }
+    let biases = to_vector(&biases, size, 0.);
+
+    let model = gnn::train(&edges, &biases, &labels, runs, steps);
+    eprintln!("error: {}", model.error);
+    output(options, &peers, &model.scores)
+}
@@ -0,0 +1,372 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use crate::linalg::Scalar;
+use crate::sparse::CsrMatrix;
+
+// Domain separation between leaves, inner nodes and the input hash
+const LEAF: u8 = 0;
+const NODE: u8 = 1;
+const INPUTS: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hash(pub [u8; 32]);
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseHashError {
+    Length(usize),
+    Digit(char),
+}
+
+impl fmt::Display for ParseHashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseHashError::Length(len) => {
+                write!(f, "expected 64 hex digits, found {} characters", len)
+            }
+            ParseHashError::Digit(c) => write!(f, "invalid hex digit `{}`", c),
+        }
+    }
+}
+
+impl Error for ParseHashError {}
+
+// Parse the hex form written by `Display`
+impl FromStr for Hash {
+    type Err = ParseHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .chars()
+            .map(|c| c.to_digit(16).ok_or(ParseHashError::Digit(c)))
+            .collect::<Result<Vec<u32>, _>>()?;
+        if digits.len() != 64 {
+            return Err(ParseHashError::Length(digits.len()));
+        }
+        let mut bytes = [0; 32];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+            *byte = (pair[0] * 16 + pair[1]) as u8;
+        }
+        Ok(Hash(bytes))
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+// Byte encoding of a score inside a leaf - little endian, so it is the same on every platform
+pub trait Commit {
+    fn commit_bytes(&self) -> Vec<u8>;
+}
+
+macro_rules! impl_commit {
+    ($($t:ty),*) => {
+        $(impl Commit for $t {
+            fn commit_bytes(&self) -> Vec<u8> {
+                self.to_le_bytes().to_vec()
+            }
+        })*
+    };
+}
+
+impl_commit!(f32, f64, u64, i64);
+
+fn hash_bytes(prefix: u8, parts: &[&[u8]]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([prefix]);
+    for part in parts {
+        hasher.update(part);
+    }
+    Hash(hasher.finalize().into())
+}
+
+fn leaf_hash<T: Commit>(peer: &str, score: &T) -> Hash {
+    let id_len = (peer.len() as u64).to_le_bytes();
+    hash_bytes(LEAF, &[&id_len, peer.as_bytes(), &score.commit_bytes()])
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    hash_bytes(NODE, &[&left.0, &right.0])
+}
+
+// Hash of everything a run depends on: peer ids, local trust, local distrust, pre-trust and
+// the algorithm parameters (any serialisable config)
+pub fn hash_inputs<T: Scalar + Commit, C: Serialize>(
+    peers: &[String],
+    lt: &CsrMatrix<T>,
+    ld: &CsrMatrix<T>,
+    pre_trust: &[T],
+    config: &C,
+) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([INPUTS]);
+    hasher.update((peers.len() as u64).to_le_bytes());
+    for peer in peers {
+        hasher.update((peer.len() as u64).to_le_bytes());
+        hasher.update(peer.as_bytes());
+    }
+    for m in [lt, ld] {
+        hasher.update((m.nnz() as u64).to_le_bytes());
+        for (i, j, v) in m.entries() {
+            hasher.update((i as u64).to_le_bytes());
+            hasher.update((j as u64).to_le_bytes());
+            hasher.update(v.commit_bytes());
+        }
+    }
+    for v in pre_trust {
+        hasher.update(v.commit_bytes());
+    }
+    hasher.update(serde_json::to_vec(config).unwrap());
+    Hash(hasher.finalize().into())
+}
+
+// What a run publishes - consumers recompute `inputs` from the data they were given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Commitment {
+    pub inputs: Hash,
+    pub root: Hash,
+}
+
+// Merkle tree over `(peer id, score)` leaves, sorted by peer id. A node without a sibling is
+// carried up to the next level unchanged.
+#[derive(Debug, Clone)]
+pub struct ScoreTree {
+    ids: Vec<String>,
+    levels: Vec<Vec<Hash>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+// Sibling hashes from the leaf up to the root, and which side each one is on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proof {
+    pub peer: String,
+    pub path: Vec<(Side, Hash)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeError {
+    Length { peers: usize, scores: usize },
+    // A peer id appears more than once, so its proof would be ambiguous
+    Duplicate(String),
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeError::Length { peers, scores } => {
+                write!(f, "{} peers but {} scores", peers, scores)
+            }
+            TreeError::Duplicate(peer) => write!(f, "duplicate peer id `{}`", peer),
+        }
+    }
+}
+
+impl Error for TreeError {}
+
+impl ScoreTree {
+    pub fn new<T: Commit>(peers: &[String], scores: &[T]) -> Result<Self, TreeError> {
+        if peers.len() != scores.len() {
+            return Err(TreeError::Length {
+                peers: peers.len(),
+                scores: scores.len(),
+            });
+        }
+        let mut leaves: Vec<(&String, &T)> = peers.iter().zip(scores).collect();
+        leaves.sort_by(|a, b| a.0.cmp(b.0));
+        if let Some(pair) = leaves.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(TreeError::Duplicate(pair[0].0.clone()));
+        }
+
+        let ids = leaves.iter().map(|(peer, _)| peer.to_string()).collect();
+        let mut levels = vec![leaves
+            .iter()
+            .map(|(peer, score)| leaf_hash(peer, *score))
+            .collect::<Vec<Hash>>()];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+
+        Ok(Self { ids, levels })
+    }
+
+    // Root of an empty tree is the hash of no leaves at all
+    pub fn root(&self) -> Hash {
+        match self.levels.last().unwrap().first() {
+            Some(root) => *root,
+            None => hash_bytes(LEAF, &[]),
+        }
+    }
+
+    pub fn commitment(&self, inputs: Hash) -> Commitment {
+        Commitment {
+            inputs,
+            root: self.root(),
+        }
+    }
+
+    pub fn prove(&self, peer: &str) -> Option<Proof> {
+        let mut index = self.ids.binary_search_by(|id| id.as_str().cmp(peer)).ok()?;
+        let mut path = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                let side = if sibling < index {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                path.push((side, level[sibling]));
+            }
+            index /= 2;
+        }
+        Some(Proof {
+            peer: peer.to_string(),
+            path,
+        })
+    }
+}
+
+impl Proof {
+    // Check that `score` is the committed score of `self.peer`
+    pub fn verify<T: Commit>(&self, root: &Hash, score: &T) -> bool {
+        let mut hash = leaf_hash(&self.peer, score);
+        for (side, sibling) in &self.path {
+            hash = match side {
+                Side::Left => node_hash(sibling, &hash),
+                Side::Right => node_hash(&hash, sibling),
+            };
+        }
+        hash == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peers(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("p{}", i)).collect()
+    }
+
+    fn scores(n: usize) -> Vec<f32> {
+        (0..n).map(|i| i as f32 / 10.).collect()
+    }
+
+    #[test]
+    fn every_peer_proves_its_score() {
+        for n in [1, 2, 3, 5, 8] {
+            let (peers, scores) = (peers(n), scores(n));
+            let tree = ScoreTree::new(&peers, &scores).unwrap();
+            let root = tree.root();
+            for (peer, score) in peers.iter().zip(&scores) {
+                let proof = tree.prove(peer).unwrap();
+                assert!(proof.verify(&root, score), "{} of {} leaves", peer, n);
+            }
+        }
+    }
+
+    #[test]
+    fn single_leaf_is_the_root() {
+        let tree = ScoreTree::new(&peers(1), &scores(1)).unwrap();
+        let proof = tree.prove("p0").unwrap();
+        assert!(proof.path.is_empty());
+        assert!(proof.verify(&tree.root(), &0f32));
+    }
+
+    #[test]
+    fn tampered_score_fails() {
+        let (peers, scores) = (peers(5), scores(5));
+        let tree = ScoreTree::new(&peers, &scores).unwrap();
+        let proof = tree.prove("p3").unwrap();
+        assert!(!proof.verify(&tree.root(), &(scores[3] + 0.01)));
+    }
+
+    #[test]
+    fn wrong_peer_fails() {
+        let (peers, scores) = (peers(3), scores(3));
+        let tree = ScoreTree::new(&peers, &scores).unwrap();
+        let mut proof = tree.prove("p2").unwrap();
+        proof.peer = "p1".to_string();
+        assert!(!proof.verify(&tree.root(), &scores[1]));
+        assert!(!proof.verify(&tree.root(), &scores[2]));
+        assert!(tree.prove("p9").is_none());
+    }
+
+    #[test]
+    fn commitment_and_proof_round_trip() {
+        let tree = ScoreTree::new(&peers(3), &scores(3)).unwrap();
+        let commitment = tree.commitment(tree.root());
+        let json = serde_json::to_string(&commitment).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Commitment>(&json).unwrap(),
+            commitment
+        );
+
+        let proof = tree.prove("p1").unwrap();
+        let json = serde_json::to_string(&proof).unwrap();
+        let parsed: Proof = serde_json::from_str(&json).unwrap();
+        assert!(parsed.verify(&commitment.root, &0.1f32));
+
+        let hex = commitment.root.to_string();
+        assert_eq!(hex.parse::<Hash>().unwrap(), commitment.root);
+        assert_eq!("ab".parse::<Hash>(), Err(ParseHashError::Length(2)));
+        assert_eq!(
+            hex.replacen(&hex[..1], "g", 1).parse::<Hash>(),
+            Err(ParseHashError::Digit('g'))
+        );
+    }
+
+    #[test]
+    fn mismatched_and_duplicate_peers_are_errors() {
+        assert_eq!(
+            ScoreTree::new(&peers(3), &scores(2)).unwrap_err(),
+            TreeError::Length {
+                peers: 3,
+                scores: 2
+            }
+        );
+        let peers = vec!["b".to_string(), "a".to_string(), "b".to_string()];
+        assert_eq!(
+            ScoreTree::new(&peers, &scores(3)).unwrap_err(),
+            TreeError::Duplicate("b".to_string())
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::eigen_trust::EigenTrustConfig;
+use crate::fixed_point::FixedPointConfig;
+use crate::hubs_and_auth::HitsConfig;
+use crate::page_rank::PageRankConfig;
+use crate::snap::SnapConfig;
+
+// Parameters for every algorithm - sections and fields left out of a file keep their defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub eigen_trust: EigenTrustConfig,
+    pub fixed_point: FixedPointConfig,
+    pub page_rank: PageRankConfig,
+    pub hits: HitsConfig,
+    pub snap: SnapConfig,
+}
+
+impl Config {
+    // Load from a `.json` file, or TOML for any other extension
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("json") => serde_json::from_str(&content).map_err(ConfigError::Json),
+            _ => toml::from_str(&content).map_err(ConfigError::Toml),
+        }
+        .and_then(|config: Self| config.validate().map(|_| config))
+    }
+
+    // Values that parse but can't be used, e.g. a pre-trust weight above 1
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.eigen_trust
+            .validate()
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        self.page_rank
+            .validate()
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        self.hits
+            .validate()
+            .map_err(|e| ConfigError::Invalid(format!("invalid hits config: {}", e)))?;
+        self.fixed_point
+            .validate()
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        self.snap
+            .validate()
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        self.snap
+            .beta
+            .validate()
+            .map_err(|e| ConfigError::Invalid(e.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "failed to read config: {}", e),
+            ConfigError::Toml(e) => write!(f, "invalid TOML config: {}", e),
+            ConfigError::Json(e) => write!(f, "invalid JSON config: {}", e),
+            ConfigError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Toml(e) => Some(e),
+            ConfigError::Json(e) => Some(e),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(toml: &str) -> Result<(), ConfigError> {
+        toml::from_str::<Config>(toml).unwrap().validate()
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn out_of_range_parameters_are_rejected() {
+        for toml in [
+            "[eigen_trust]\npre_trust_weight = 7.0",
+            "[eigen_trust]\ntolerance = -1.0",
+            "[eigen_trust]\ndistrust = { k_hop = { hops = 2, decay = 1.5 } }",
+            "[page_rank]\ndampening_amount = -0.1",
+            "[page_rank]\ntolerance = inf",
+            "[hits]\ntolerance = nan",
+            "[fixed_point]\npre_trust_weight = 2.0",
+            "[snap]\nreport_threshold = 0.8",
+            "[snap]\npolicy = { symmetric = { threshold = 0.7 } }",
+            "[snap.beta]\ncredible_mass = 1.0",
+            "[snap.beta]\ndecision = -0.5",
+            "[snap.beta]\nmax_width = nan",
+        ] {
+            assert!(
+                matches!(validate(toml), Err(ConfigError::Invalid(_))),
+                "{}",
+                toml
+            );
+        }
+    }
+}
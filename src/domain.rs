@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::eigen_trust::{self, EigenTrustConfig, EigenTrustError, Scores};
+use crate::linalg::{cast, normalise, vec_add, vec_scalar_mul, Norm, Scalar, Vector};
+use crate::sparse::CsrMatrix;
+
+// A trust domain, e.g. "Software Security", with its own opinions and pre-trusted peers.
+// Every domain covers the same peers, so scores line up across domains.
+#[derive(Debug, Clone)]
+pub struct Domain<T = f32> {
+    pub name: String,
+    pub lt: CsrMatrix<T>,
+    pub ld: CsrMatrix<T>,
+    pub pre_trust: Vector<T>,
+    pub transfer: Option<Transfer>,
+}
+
+// Borrow pre-trust from the final scores of another domain
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub from: String,
+    // Share of the pre-trust taken from the other domain - 1 replaces the domain's own pre-trust
+    pub weight: f64,
+}
+
+impl<T: Scalar> Domain<T> {
+    pub fn new(name: &str, lt: CsrMatrix<T>, ld: CsrMatrix<T>, pre_trust: Vector<T>) -> Self {
+        Self {
+            name: name.to_string(),
+            lt,
+            ld,
+            pre_trust,
+            transfer: None,
+        }
+    }
+
+    pub fn with_transfer(mut self, from: &str, weight: f64) -> Self {
+        self.transfer = Some(Transfer {
+            from: from.to_string(),
+            weight,
+        });
+        self
+    }
+
+    fn run(&self, pre_trust: &[T], config: &EigenTrustConfig) -> Result<Scores<T>, DomainError> {
+        eigen_trust::run(&self.lt, &self.ld, pre_trust, config).map_err(|error| {
+            DomainError::Invalid {
+                domain: self.name.clone(),
+                error,
+            }
+        })
+    }
+}
+
+// (1 - weight) * pre_trust + weight * scores, where negative scores count as zero and both
+// sides are scaled to a distribution first. A domain without any pre-trust of its own simply
+// starts from the other domain's scores. Both sides must cover the same peers and `weight` must be
+// in [0, 1] - `run_domains` checks both before blending.
+fn blend_pre_trust<T: Scalar>(pre_trust: &[T], scores: &[T], weight: f64) -> Vector<T> {
+    let weight: T = cast(weight);
+    let own = normalise(pre_trust, Norm::L1, None);
+    let borrowed: Vector<T> = scores.iter().map(|x| x.max(T::zero())).collect();
+    let borrowed = normalise(&borrowed, Norm::L1, None);
+    let blended = vec_add(
+        &vec_scalar_mul(&own, T::one() - weight),
+        &vec_scalar_mul(&borrowed, weight),
+    );
+    normalise(&blended, Norm::L1, Some(&own))
+}
+
+// Scores of every domain, keyed by domain name
+pub type DomainScores<T = f32> = BTreeMap<String, Scores<T>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DomainError {
+    Duplicate(String),
+    UnknownSource {
+        domain: String,
+        from: String,
+    },
+    // Domains that transfer pre-trust from each other in a loop
+    Cycle(Vec<String>),
+    // Local trust, local distrust and pre-trust don't cover the same number of peers
+    Size {
+        domain: String,
+        trust: usize,
+        distrust: usize,
+        pre_trust: usize,
+    },
+    // Transfer weight outside [0, 1]
+    Weight {
+        domain: String,
+        weight: f64,
+    },
+    // The domain borrows pre-trust from a domain over a different number of peers
+    SourceSize {
+        domain: String,
+        from: String,
+        size: usize,
+        from_size: usize,
+    },
+    Invalid {
+        domain: String,
+        error: EigenTrustError,
+    },
+}
+
+impl fmt::Display for DomainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainError::Duplicate(domain) => write!(f, "domain `{}` is defined twice", domain),
+            DomainError::UnknownSource { domain, from } => write!(
+                f,
+                "domain `{}` transfers pre-trust from unknown domain `{}`",
+                domain, from
+            ),
+            DomainError::Cycle(domains) => write!(
+                f,
+                "domains transfer pre-trust in a cycle: {}",
+                domains.join(", ")
+            ),
+            DomainError::Size {
+                domain,
+                trust,
+                distrust,
+                pre_trust,
+            } => write!(
+                f,
+                "domain `{}` has {} peers in local trust, {} in local distrust and {} in pre-trust",
+                domain, trust, distrust, pre_trust
+            ),
+            DomainError::Weight { domain, weight } => write!(
+                f,
+                "domain `{}` transfers pre-trust with weight {}, expected a value in [0, 1]",
+                domain, weight
+            ),
+            DomainError::SourceSize {
+                domain,
+                from,
+                size,
+                from_size,
+            } => write!(
+                f,
+                "domain `{}` has {} peers but transfers pre-trust from domain `{}` with {}",
+                domain, size, from, from_size
+            ),
+            DomainError::Invalid { domain, error } => write!(f, "domain `{}`: {}", domain, error),
+        }
+    }
+}
+
+impl Error for DomainError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DomainError::Duplicate(_) => None,
+            DomainError::UnknownSource { .. } => None,
+            DomainError::Cycle(_) => None,
+            DomainError::Size { .. } => None,
+            DomainError::Weight { .. } => None,
+            DomainError::SourceSize { .. } => None,
+            DomainError::Invalid { error, .. } => Some(error),
+        }
+    }
+}
+
+// Run every domain with the same parameters. Domains that transfer pre-trust run after the
+// domain they borrow from.
+pub fn run_domains<T: Scalar>(
+    domains: &[Domain<T>],
+    config: &EigenTrustConfig,
+) -> Result<DomainScores<T>, DomainError> {
+    let mut by_name = BTreeMap::new();
+    for domain in domains {
+        if by_name.insert(domain.name.as_str(), domain).is_some() {
+            return Err(DomainError::Duplicate(domain.name.clone()));
+        }
+        let size = domain.pre_trust.len();
+        if domain.lt.size() != size || domain.ld.size() != size {
+            return Err(DomainError::Size {
+                domain: domain.name.clone(),
+                trust: domain.lt.size(),
+                distrust: domain.ld.size(),
+                pre_trust: size,
+            });
+        }
+    }
+    for domain in domains {
+        let Some(transfer) = &domain.transfer else {
+            continue;
+        };
+        let Some(from) = by_name.get(transfer.from.as_str()) else {
+            return Err(DomainError::UnknownSource {
+                domain: domain.name.clone(),
+                from: transfer.from.clone(),
+            });
+        };
+        if !(0. ..=1.).contains(&transfer.weight) {
+            return Err(DomainError::Weight {
+                domain: domain.name.clone(),
+                weight: transfer.weight,
+            });
+        }
+        if from.pre_trust.len() != domain.pre_trust.len() {
+            return Err(DomainError::SourceSize {
+                domain: domain.name.clone(),
+                from: transfer.from.clone(),
+                size: domain.pre_trust.len(),
+                from_size: from.pre_trust.len(),
+            });
+        }
+    }
+
+    let mut scores = DomainScores::new();
+    let mut pending: Vec<&Domain<T>> = domains.iter().collect();
+    while !pending.is_empty() {
+        let mut waiting = Vec::new();
+        for domain in pending.iter().copied() {
+            let pre_trust = match &domain.transfer {
+                None => domain.pre_trust.clone(),
+                Some(transfer) => match scores.get(&transfer.from) {
+                    Some(from) => {
+                        blend_pre_trust(&domain.pre_trust, &from.adjusted, transfer.weight)
+                    }
+                    None => {
+                        waiting.push(domain);
+                        continue;
+                    }
+                },
+            };
+            let domain_scores = domain.run(&pre_trust, config)?;
+            scores.insert(domain.name.clone(), domain_scores);
+        }
+        if waiting.len() == pending.len() {
+            let names = waiting.iter().map(|domain| domain.name.clone()).collect();
+            return Err(DomainError::Cycle(names));
+        }
+        pending = waiting;
+    }
+    Ok(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain(name: &str, size: usize) -> Domain {
+        let lt = CsrMatrix::from_triplets(size, vec![(0, 1, 1.), (1, 0, 1.)]);
+        let ld = CsrMatrix::from_triplets(size, vec![]);
+        let mut pre_trust = vec![0.; size];
+        pre_trust[0] = 1.;
+        Domain::new(name, lt, ld, pre_trust)
+    }
+
+    #[test]
+    fn domain_sizes_must_agree() {
+        let mut bad = domain("b", 3);
+        bad.pre_trust.push(0.);
+        let error = run_domains(&[domain("a", 3), bad], &EigenTrustConfig::default()).unwrap_err();
+        assert_eq!(
+            error,
+            DomainError::Size {
+                domain: "b".to_string(),
+                trust: 3,
+                distrust: 3,
+                pre_trust: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn transfers_are_checked() {
+        let config = EigenTrustConfig::default();
+        for weight in [-0.1, 1.5, f64::NAN] {
+            let domains = [domain("a", 3), domain("b", 3).with_transfer("a", weight)];
+            match run_domains(&domains, &config) {
+                Err(DomainError::Weight { domain, .. }) => assert_eq!(domain, "b"),
+                other => panic!("expected a weight error, found {:?}", other),
+            }
+        }
+
+        let domains = [domain("a", 4), domain("b", 3).with_transfer("a", 0.5)];
+        assert_eq!(
+            run_domains(&domains, &config).unwrap_err(),
+            DomainError::SourceSize {
+                domain: "b".to_string(),
+                from: "a".to_string(),
+                size: 3,
+                from_size: 4,
+            }
+        );
+
+        let domains = [domain("a", 3), domain("b", 3).with_transfer("a", 1.)];
+        assert!(run_domains(&domains, &config).is_ok());
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-6, "{:?} != {:?}", a, b);
+        }
+    }
+
+    // `a` distrusts peer 2 enough for its adjusted score to go negative, and `b` pre-trusts
+    // peer 1 instead of peer 0
+    fn blended(weight: f64) -> (Scores, Domain) {
+        let mut a = domain("a", 3);
+        a.ld = CsrMatrix::from_triplets(3, vec![(0, 2, 1.)]);
+        let mut b = domain("b", 3).with_transfer("a", weight);
+        b.pre_trust = vec![0., 1., 0.];
+        let config = EigenTrustConfig::default();
+        let mut scores = run_domains(&[a, b.clone()], &config).unwrap();
+        (scores.remove("b").unwrap(), b)
+    }
+
+    #[test]
+    fn full_transfer_replaces_pre_trust() {
+        let mut a = domain("a", 3);
+        a.ld = CsrMatrix::from_triplets(3, vec![(0, 2, 1.)]);
+        let config = EigenTrustConfig::default();
+        let from = eigen_trust::run(&a.lt, &a.ld, &a.pre_trust, &config).unwrap();
+        assert!(from.adjusted[2] < 0.);
+        let clamped: Vector = from.adjusted.iter().map(|x| x.max(0.)).collect();
+        let pre_trust = normalise(&clamped, Norm::L1, None);
+
+        let (scores, b) = blended(1.);
+        let expected = eigen_trust::run(&b.lt, &b.ld, &pre_trust, &config).unwrap();
+        assert_close(&scores.trust, &expected.trust);
+    }
+
+    #[test]
+    fn zero_transfer_keeps_pre_trust() {
+        let config = EigenTrustConfig::default();
+        let (scores, b) = blended(0.);
+        let expected = eigen_trust::run(&b.lt, &b.ld, &b.pre_trust, &config).unwrap();
+        assert_close(&scores.trust, &expected.trust);
+    }
+}
@@ -1,406 +1,981 @@
-const NUM_NEIGHBOURS: usize = 5;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{run_domains, Domain};
+use crate::linalg::{cast, normalise, vec_add, vec_scalar_mul, Norm, Scalar, Vector};
+use crate::snap::{
+    calculate_beta_snap_score, calculate_snap_score_with_threshold, Attestation, Ledger,
+    SnapConfig, SnapHistory, SnapRegistry, Thresholds,
+};
+use crate::sparse::CsrMatrix;
+use crate::utils::{format_vec, Convergence, ConvergenceReport, Execution, Residual};
+
 const NUM_ITER: usize = 30;
-const PRE_TRUST_WEIGHT: f32 = 0.5;
-
-const CONFIDENCE_THRESHOLD: f32 = 0.3;
-const SECURE_THRESHOLD: f32 = 0.7;
-
-fn validate_lt(lt: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS]) {
-    // Compute sum of incoming distrust
-    for i in 0..NUM_NEIGHBOURS {
-        for j in 0..NUM_NEIGHBOURS {
-            // Make sure we are not giving score to ourselves
-            if i == j {
-                assert_eq!(lt[i][j], 0.);
+const TOLERANCE: f64 = 1e-6;
+const PRE_TRUST_WEIGHT: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EigenTrustConfig {
+    pub pre_trust_weight: f64,
+    pub max_iter: usize,
+    pub tolerance: f64,
+    pub residual: Residual,
+    pub execution: Execution,
+    pub distrust: DistrustModel,
+    pub adjustment: AdjustmentPolicy,
+}
+
+impl Default for EigenTrustConfig {
+    fn default() -> Self {
+        Self {
+            pre_trust_weight: PRE_TRUST_WEIGHT,
+            max_iter: NUM_ITER,
+            tolerance: TOLERANCE,
+            residual: Residual::L1,
+            execution: Execution::Sequential,
+            distrust: DistrustModel::OneHop,
+            adjustment: AdjustmentPolicy::AllowNegative,
+        }
+    }
+}
+
+impl EigenTrustConfig {
+    pub fn validate(&self) -> Result<(), EigenTrustError> {
+        if !(0. ..=1.).contains(&self.pre_trust_weight) {
+            return Err(EigenTrustError::Config(format!(
+                "pre_trust_weight must be in [0, 1], found {}",
+                self.pre_trust_weight
+            )));
+        }
+        if let DistrustModel::KHop { hops, decay } = self.distrust {
+            if hops == 0 {
+                return Err(EigenTrustError::Config(
+                    "k_hop hops must be at least 1".to_string(),
+                ));
+            }
+            if !(0. ..=1.).contains(&decay) {
+                return Err(EigenTrustError::Config(format!(
+                    "k_hop decay must be in [0, 1], found {}",
+                    decay
+                )));
             }
-            assert!(lt[i][j] >= 0.);
+        }
+        self.convergence()
+            .validate()
+            .map_err(EigenTrustError::Config)
+    }
+
+    pub fn convergence(&self) -> Convergence {
+        Convergence {
+            tolerance: self.tolerance,
+            max_iter: self.max_iter,
+            residual: self.residual,
         }
     }
 }
 
-fn validate_lt_overlap(
-    lt: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS],
-    ld: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS],
-) {
-    // Compute sum of incoming distrust
-    for i in 0..NUM_NEIGHBOURS {
-        for j in 0..NUM_NEIGHBOURS {
-            let trust_zero = lt[i][j] == 0.0;
-            let distrust_zero = ld[i][j] == 0.0;
-            assert!(trust_zero || distrust_zero);
+// How distrust spreads from the peers who express it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistrustModel {
+    // Distrust from trusted peers counts once
+    OneHop,
+    // Distrusted peers also pass distrust on to the peers they trust, scaled by `decay` at
+    // every further hop
+    KHop { hops: usize, decay: f64 },
+    // Distrust is weighted by the adjusted scores instead of trust alone, repeated until the
+    // adjusted scores converge - peers with a negative adjusted score carry no weight
+    Iterative,
+}
+
+// How trust and distrust are combined into the adjusted scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdjustmentPolicy {
+    // s - sd, negative where distrust outweighs trust
+    AllowNegative,
+    // s - sd, floored at zero
+    ClampZero,
+    // s - sd floored at zero, then scaled back to a distribution
+    ClampRenormalise,
+    // s * (1 - sd / (s + sd)) = s^2 / (s + sd) - distrust removes the share of the peer's
+    // opinions that are distrust, so scores shrink smoothly and never reach zero while s > 0
+    Multiplicative,
+}
+
+impl fmt::Display for AdjustmentPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdjustmentPolicy::AllowNegative => write!(f, "allow_negative"),
+            AdjustmentPolicy::ClampZero => write!(f, "clamp_zero"),
+            AdjustmentPolicy::ClampRenormalise => write!(f, "clamp_renormalise"),
+            AdjustmentPolicy::Multiplicative => write!(f, "multiplicative"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opinion {
+    Trust,
+    Distrust,
+    // A peer's own pre-trust, reported with `from` and `to` both set to the peer
+    PreTrust,
+}
+
+// Rule broken by a single local trust or distrust cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    SelfTrust,
+    Negative,
+    NonFinite,
+    // The peer also distrusts the same target with the given amount
+    TrustDistrustOverlap { distrust: f32 },
+    // A row update names a peer outside the `size` peers of the matrix
+    OutOfRange { size: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Violation {
+    pub opinion: Opinion,
+    pub from: usize,
+    pub to: usize,
+    pub value: f32,
+    pub rule: Rule,
+}
+
+impl fmt::Display for Opinion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Opinion::Trust => write!(f, "trust"),
+            Opinion::Distrust => write!(f, "distrust"),
+            Opinion::PreTrust => write!(f, "pre-trust"),
         }
     }
 }
 
-fn normalise(
-    lt_vec: [f32; NUM_NEIGHBOURS],
-    pre_trust: [f32; NUM_NEIGHBOURS],
-) -> [f32; NUM_NEIGHBOURS] {
-    let sum: f32 = lt_vec.iter().sum();
-    if sum == 0. {
-        return pre_trust;
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::SelfTrust => write!(f, "peer rates itself"),
+            Rule::Negative => write!(f, "negative value"),
+            Rule::NonFinite => write!(f, "value is not finite"),
+            Rule::TrustDistrustOverlap { distrust } => {
+                write!(f, "peer also distrusts the target ({})", distrust)
+            }
+            Rule::OutOfRange { size } => write!(f, "peer index out of range for {} peers", size),
+        }
     }
-    lt_vec.map(|x| x / sum)
 }
 
-fn vec_scalar_mul(s: [f32; NUM_NEIGHBOURS], y: f32) -> [f32; NUM_NEIGHBOURS] {
-    s.map(|x| x * y)
+impl Violation {
+    // Message with each peer index shown as `id(index)`, e.g. the peer's own identifier
+    pub fn describe<D: fmt::Display>(&self, id: impl Fn(usize) -> D) -> String {
+        if self.opinion == Opinion::PreTrust {
+            return format!(
+                "{} {} = {}: {}",
+                self.opinion,
+                id(self.from),
+                self.value,
+                self.rule
+            );
+        }
+        format!(
+            "{} {} -> {} = {}: {}",
+            self.opinion,
+            id(self.from),
+            id(self.to),
+            self.value,
+            self.rule
+        )
+    }
 }
 
-fn vec_add(s: [f32; NUM_NEIGHBOURS], y: [f32; NUM_NEIGHBOURS]) -> [f32; NUM_NEIGHBOURS] {
-    let mut out: [f32; NUM_NEIGHBOURS] = [0.; NUM_NEIGHBOURS];
-    for i in 0..NUM_NEIGHBOURS {
-        out[i] = s[i] + y[i];
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(|i| i))
     }
-    out
 }
 
-fn transpose(
-    s: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS],
-) -> [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] {
-    let mut new_s: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [[0.; NUM_NEIGHBOURS]; NUM_NEIGHBOURS];
-    for i in 0..NUM_NEIGHBOURS {
-        for j in 0..NUM_NEIGHBOURS {
-            new_s[i][j] = s[j][i];
+// Every offending cell found in the local trust and distrust matrices, or in the pre-trust
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationError {
+    pub(crate) fn check(violations: Vec<Violation>) -> Result<(), Self> {
+        if violations.is_empty() {
+            return Ok(());
         }
+        Err(Self { violations })
     }
-    new_s
-}
-
-fn calculate_snap_score(
-    st: [f32; NUM_NEIGHBOURS],
-    sd: [f32; NUM_NEIGHBOURS],
-    s: [f32; NUM_NEIGHBOURS],
-) -> f32 {
-    let num: f32 = st
-        .iter()
-        .zip(s)
-        .map(|(x, y)| if *x == 50. { y } else { 0. })
-        .sum();
-    let den: f32 = sd
-        .iter()
-        .zip(s)
-        .map(|(x, y)| if *x == 50. { y } else { 0. })
-        .sum();
-    let snap_score: f32 = num / (num + den);
-
-    let score = if snap_score.is_nan() { 0. } else { snap_score };
-
-    score
-}
-
-#[derive(Debug)]
-enum State {
-    Reported,
-    Contested,
-    Endorsed,
-    Unverified,
-}
-
-fn calculate_snap_score_with_threshold(
-    st: [f32; NUM_NEIGHBOURS],
-    sd: [f32; NUM_NEIGHBOURS],
-    s: [f32; NUM_NEIGHBOURS],
-    threshold: f32,
-) -> (f32, f32, State) {
-    /* x: positive rating from peer to snap */
-    /* y: the peer's score */
-    let num: f32 = st
-        .iter()
-        .zip(s)
-        .map(|(x, y)| {
-            if y < 0.0 {
-                return 0.0;
-            }
-            if *x == 50. {
-                y
-            } else {
-                0.
-            }
-        })
-        .sum();
-    // num: sum of positive peer scores who said yes
-    let den: f32 = sd
-        .iter()
-        .zip(s)
-        .map(|(x, y)| {
-            if y < 0.0 {
-                return 0.0;
-            }
-            if *x == 50. {
-                y
-            } else {
-                0.
-            }
-        })
-        .sum();
-    // num: sum of positive peer scores who said no
-    let snap_score: f32 = num / (num + den);
-
-    let score = if snap_score.is_nan() { 0. } else { snap_score };
-    let confidence = num + den;
-
-    let upper_threshold = 1. - threshold;
-    let state = match (score, confidence) {
-        (_, c) if c <= threshold => State::Unverified,
-        (s, c) if c > threshold && s <= threshold => State::Reported,
-        (s, c) if c > threshold && s > threshold && s <= upper_threshold => State::Contested,
-        (s, c) if c > threshold && s > upper_threshold => State::Endorsed,
-        (_, _) => State::Unverified,
-    };
 
-    (score, confidence, state)
+    // Message with each peer index shown as `id(index)`, like `Violation::describe`
+    pub fn describe<D: fmt::Display>(&self, id: impl Fn(usize) -> D) -> String {
+        let mut message = format!("{} invalid trust entries", self.violations.len());
+        for violation in &self.violations {
+            message += "\n  ";
+            message += &violation.describe(&id);
+        }
+        message
+    }
 }
 
-// Calculate threshold
-fn calculate_snap_score_threshold(pre_trust: [f32; NUM_NEIGHBOURS]) -> f32 {
-    let non_zero = pre_trust
-        .into_iter()
-        .filter(|x| *x != 0.)
-        .collect::<Vec<f32>>();
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(|i| i))
+    }
+}
+
+impl Error for ValidationError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EigenTrustError {
+    Config(String),
+    Invalid(ValidationError),
+    // The seed of a warm start doesn't have one score per peer
+    SeedLength {
+        expected: usize,
+        found: usize,
+    },
+    // Local distrust or pre-trust covers a different number of peers than local trust
+    Size {
+        name: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
 
-    let mut min = f32::MAX;
-    non_zero.into_iter().for_each(|x| {
-        if x < min {
-            min = x;
+impl fmt::Display for EigenTrustError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EigenTrustError::Config(message) => {
+                write!(f, "invalid eigen_trust config: {}", message)
+            }
+            EigenTrustError::Invalid(error) => write!(f, "{}", error),
+            EigenTrustError::SeedLength { expected, found } => {
+                write!(f, "seed has {} scores, expected {}", found, expected)
+            }
+            EigenTrustError::Size {
+                name,
+                expected,
+                found,
+            } => write!(f, "{} covers {} peers, expected {}", name, found, expected),
         }
-    });
+    }
+}
 
-    min
+impl Error for EigenTrustError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EigenTrustError::Config(_) => None,
+            EigenTrustError::Invalid(error) => Some(error),
+            EigenTrustError::SeedLength { .. } => None,
+            EigenTrustError::Size { .. } => None,
+        }
+    }
 }
 
-fn positive_run(
-    domain: String,
-    mut lt: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS],
-    pre_trust: [f32; NUM_NEIGHBOURS],
-) -> [f32; NUM_NEIGHBOURS] {
-    println!();
-    println!("{} - Trust:", domain);
+impl From<ValidationError> for EigenTrustError {
+    fn from(error: ValidationError) -> Self {
+        EigenTrustError::Invalid(error)
+    }
+}
 
-    validate_lt(lt);
-    for i in 0..NUM_NEIGHBOURS {
-        lt[i] = normalise(lt[i], pre_trust);
+// Every input must cover the `expected` peers of the local trust matrix
+fn check_size(name: &'static str, expected: usize, found: usize) -> Result<(), EigenTrustError> {
+    if found != expected {
+        return Err(EigenTrustError::Size {
+            name,
+            expected,
+            found,
+        });
     }
+    Ok(())
+}
 
-    let mut s = pre_trust.clone();
-    let pre_trusted_scores = pre_trust.map(|x| x * PRE_TRUST_WEIGHT);
+pub(crate) fn local_violations<T: Scalar>(m: &CsrMatrix<T>, opinion: Opinion) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (i, j, value) in m.entries() {
+        let mut push = |rule| {
+            violations.push(Violation {
+                opinion,
+                from: i,
+                to: j,
+                value: cast(value),
+                rule,
+            })
+        };
+        // Make sure we are not giving score to ourselves
+        if i == j {
+            push(Rule::SelfTrust);
+        }
+        if !value.is_finite() {
+            push(Rule::NonFinite);
+        } else if value < T::zero() {
+            push(Rule::Negative);
+        }
+    }
+    violations
+}
 
-    println!("start: [{}]", s.map(|v| format!("{:>9.4}", v)).join(", "));
-    for _ in 0..NUM_ITER {
-        let mut new_s = [0.; 5];
+fn overlap_violations<T: Scalar>(lt: &CsrMatrix<T>, ld: &CsrMatrix<T>) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (i, j, value) in lt.entries() {
+        let distrust = ld.get(i, j);
+        if distrust != T::zero() {
+            violations.push(Violation {
+                opinion: Opinion::Trust,
+                from: i,
+                to: j,
+                value: cast(value),
+                rule: Rule::TrustDistrustOverlap {
+                    distrust: cast(distrust),
+                },
+            });
+        }
+    }
+    violations
+}
 
-        // Compute sum of incoming weights
-        for i in 0..NUM_NEIGHBOURS {
-            for j in 0..NUM_NEIGHBOURS {
-                new_s[i] += lt[j][i] * s[j];
+// Cells of row updates that fall outside the matrix, checked before the rows are replaced
+fn update_violations<T: Scalar>(
+    updates: &[RowUpdate<T>],
+    size: usize,
+    opinion: Opinion,
+) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (i, row) in updates {
+        // Clearing a row of an unknown peer has no cell to report, so the peer stands in for it
+        if *i >= size && row.is_empty() {
+            violations.push(Violation {
+                opinion,
+                from: *i,
+                to: *i,
+                value: 0.,
+                rule: Rule::OutOfRange { size },
+            });
+        }
+        for (j, value) in row {
+            if *i >= size || *j >= size {
+                violations.push(Violation {
+                    opinion,
+                    from: *i,
+                    to: *j,
+                    value: cast(*value),
+                    rule: Rule::OutOfRange { size },
+                });
             }
         }
+    }
+    violations
+}
 
-        let global_scores = new_s.map(|x| (1. - PRE_TRUST_WEIGHT) * x);
-        let current_s = vec_add(pre_trusted_scores, global_scores);
-
-        s = current_s;
+pub(crate) fn pre_trust_violations<T: Scalar>(pre_trust: &[T]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (i, value) in pre_trust.iter().enumerate() {
+        let rule = if !value.is_finite() {
+            Rule::NonFinite
+        } else if *value < T::zero() {
+            Rule::Negative
+        } else {
+            continue;
+        };
+        violations.push(Violation {
+            opinion: Opinion::PreTrust,
+            from: i,
+            to: i,
+            value: cast(*value),
+            rule,
+        });
     }
-    println!("end: [{}]", s.map(|v| format!("{:>9.4}", v)).join(", "));
+    violations
+}
 
-    s
+pub fn validate_pre_trust<T: Scalar>(pre_trust: &[T]) -> Result<(), ValidationError> {
+    ValidationError::check(pre_trust_violations(pre_trust))
 }
 
-fn negative_run(
-    domain: String,
-    mut lt: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS],
-    s: [f32; NUM_NEIGHBOURS],
-) -> [f32; NUM_NEIGHBOURS] {
-    println!();
-    println!("{} - Distrust:", domain);
+pub fn validate_lt<T: Scalar>(lt: &CsrMatrix<T>) -> Result<(), ValidationError> {
+    ValidationError::check(local_violations(lt, Opinion::Trust))
+}
 
-    validate_lt(lt);
-    for i in 0..NUM_NEIGHBOURS {
-        lt[i] = normalise(lt[i], [0.; NUM_NEIGHBOURS]);
+pub fn validate_ld<T: Scalar>(ld: &CsrMatrix<T>) -> Result<(), ValidationError> {
+    ValidationError::check(local_violations(ld, Opinion::Distrust))
+}
+
+pub fn validate_lt_overlap<T: Scalar>(
+    lt: &CsrMatrix<T>,
+    ld: &CsrMatrix<T>,
+) -> Result<(), ValidationError> {
+    ValidationError::check(overlap_violations(lt, ld))
+}
+
+// How `sanitise` resolves a peer both trusting and distrusting the same target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    // Keep the difference on whichever side is larger
+    Net,
+    // Keep the larger opinion unchanged, ties drop both
+    KeepLarger,
+    DropBoth,
+}
+
+// A cell changed by `sanitise`, and the rule it broke
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Repair {
+    pub opinion: Opinion,
+    pub from: usize,
+    pub to: usize,
+    pub before: f32,
+    pub after: f32,
+    pub rule: Rule,
+}
+
+impl Repair {
+    // Message with each peer index shown as `id(index)`, like `Violation::describe`
+    pub fn describe<D: fmt::Display>(&self, id: impl Fn(usize) -> D) -> String {
+        format!(
+            "{} {} -> {}: {} => {} ({})",
+            self.opinion,
+            id(self.from),
+            id(self.to),
+            self.before,
+            self.after,
+            self.rule
+        )
     }
+}
 
-    let mut new_s = [0.0; NUM_NEIGHBOURS];
-    // Compute sum of incoming weights
-    for i in 0..NUM_NEIGHBOURS {
-        for j in 0..NUM_NEIGHBOURS {
-            new_s[i] += lt[j][i] * s[j];
-        }
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe(|i| i))
     }
+}
 
-    println!("end: [{}]", new_s.map(|v| format!("{:>9.4}", v)).join(", "));
-    new_s
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SanitiseReport {
+    pub repairs: Vec<Repair>,
 }
 
-fn negative_adjustment(
-    s: [f32; NUM_NEIGHBOURS],
-    sd: [f32; NUM_NEIGHBOURS],
-) -> [f32; NUM_NEIGHBOURS] {
-    let mut adjusted = [0.0f32; NUM_NEIGHBOURS];
-    for i in 0..NUM_NEIGHBOURS {
-        adjusted[i] = s[i] - sd[i];
+fn sanitise_local<T: Scalar>(
+    m: &CsrMatrix<T>,
+    opinion: Opinion,
+    report: &mut SanitiseReport,
+) -> Vec<(usize, usize, T)> {
+    let mut entries = Vec::with_capacity(m.nnz());
+    for (i, j, value) in m.entries() {
+        // Non-finite cells are dropped, self-trust is zeroed and negatives are clamped
+        let rule = if !value.is_finite() {
+            Rule::NonFinite
+        } else if i == j {
+            Rule::SelfTrust
+        } else if value < T::zero() {
+            Rule::Negative
+        } else {
+            entries.push((i, j, value));
+            continue;
+        };
+        report.repairs.push(Repair {
+            opinion,
+            from: i,
+            to: j,
+            before: cast(value),
+            after: 0.,
+            rule,
+        });
     }
+    entries
+}
 
-    println!();
-    println!("Adjusted");
-    println!(
-        "adjusted: [{}]",
-        adjusted.map(|v| format!("{:>9.4}", v)).join(", ")
+// Repair local trust and distrust so they pass validation, reporting every changed cell
+pub fn sanitise<T: Scalar>(
+    lt: &CsrMatrix<T>,
+    ld: &CsrMatrix<T>,
+    policy: OverlapPolicy,
+) -> (CsrMatrix<T>, CsrMatrix<T>, SanitiseReport) {
+    let mut report = SanitiseReport::default();
+    let lt = CsrMatrix::from_triplets(lt.size(), sanitise_local(lt, Opinion::Trust, &mut report));
+    let ld = CsrMatrix::from_triplets(
+        ld.size(),
+        sanitise_local(ld, Opinion::Distrust, &mut report),
     );
 
-    adjusted
+    let mut lt_entries = Vec::with_capacity(lt.nnz());
+    let mut ld_overrides = HashMap::new();
+    for (i, j, trust) in lt.entries() {
+        let distrust = ld.get(i, j);
+        if distrust == T::zero() {
+            lt_entries.push((i, j, trust));
+            continue;
+        }
+
+        let (new_trust, new_distrust) = match policy {
+            OverlapPolicy::Net => (
+                (trust - distrust).max(T::zero()),
+                (distrust - trust).max(T::zero()),
+            ),
+            OverlapPolicy::KeepLarger if trust > distrust => (trust, T::zero()),
+            OverlapPolicy::KeepLarger if distrust > trust => (T::zero(), distrust),
+            OverlapPolicy::KeepLarger | OverlapPolicy::DropBoth => (T::zero(), T::zero()),
+        };
+        let rule = Rule::TrustDistrustOverlap {
+            distrust: cast(distrust),
+        };
+        for (opinion, before, after) in [
+            (Opinion::Trust, trust, new_trust),
+            (Opinion::Distrust, distrust, new_distrust),
+        ] {
+            if before != after {
+                report.repairs.push(Repair {
+                    opinion,
+                    from: i,
+                    to: j,
+                    before: cast(before),
+                    after: cast(after),
+                    rule,
+                });
+            }
+        }
+
+        lt_entries.push((i, j, new_trust));
+        ld_overrides.insert((i, j), new_distrust);
+    }
+    let ld_entries = ld
+        .entries()
+        .map(|(i, j, value)| (i, j, *ld_overrides.get(&(i, j)).unwrap_or(&value)))
+        .collect();
+
+    let lt = CsrMatrix::from_triplets(lt.size(), lt_entries);
+    let ld = CsrMatrix::from_triplets(ld.size(), ld_entries);
+    (lt, ld, report)
 }
 
-fn functional_case() {
-    let pre_trust: [f32; NUM_NEIGHBOURS] = [0.0, 0.0, 0.0, 0.7, 0.3];
-    let snap_threshold = calculate_snap_score_threshold(pre_trust);
-    println!("security_threshold: {}", snap_threshold);
+#[derive(Debug, Clone)]
+pub struct Scores<T = f32> {
+    pub trust: Vector<T>,
+    pub distrust: Vector<T>,
+    pub adjusted: Vector<T>,
+    // The policy `adjusted` was computed with
+    pub adjustment: AdjustmentPolicy,
+    pub report: ConvergenceReport,
+    // Only set for distrust models that iterate
+    pub distrust_report: Option<ConvergenceReport>,
+}
 
-    let lt_ss: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 1.0, 0.0, 0.0], // - Peer 0 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
-        [11., 0.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
-        [0.0, 10., 0.0, 0.0, 0.0], // = Peer 4 opinions
-    ];
-    let ld_ss: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 0 opinions
-        [0.0, 0.0, 10., 0.0, 0.0], // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
-        [0.0, 1.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
-        [10., 0.0, 0.0, 0.0, 0.0], // = Peer 4 opinions
-    ];
-    validate_lt_overlap(lt_ss, ld_ss);
-    let lt_sd: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 1.0, 0.0, 0.0], // - Peer 0 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
-        [1.0, 0.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // = Peer 4 opinions
-    ];
-    let ld_sd: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 0 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
-        [0.0, 1.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0], // = Peer 4 opinions
-    ];
-    validate_lt_overlap(lt_sd, ld_sd);
+// Trust, distrust and the adjusted scores for a single domain
+// Generic over the float type - `f32` for the fast path, `f64` where rounding drift matters
+pub fn run<T: Scalar>(
+    lt: &CsrMatrix<T>,
+    ld: &CsrMatrix<T>,
+    pre_trust: &[T],
+    config: &EigenTrustConfig,
+) -> Result<Scores<T>, EigenTrustError> {
+    config.validate()?;
+    check_size("local distrust", lt.size(), ld.size())?;
+    check_size("pre-trust", lt.size(), pre_trust.len())?;
+    let mut violations = local_violations(lt, Opinion::Trust);
+    violations.extend(local_violations(ld, Opinion::Distrust));
+    violations.extend(overlap_violations(lt, ld));
+    ValidationError::check(violations)?;
+
+    let (trust, report) = iterate_trust(lt, pre_trust, pre_trust, config);
+    let (distrust, distrust_report) = propagate_distrust(lt, ld, &trust, config)?;
+    let adjusted = adjust(&trust, &distrust, config.adjustment);
+
+    Ok(Scores {
+        trust,
+        distrust,
+        adjusted,
+        adjustment: config.adjustment,
+        report,
+        distrust_report,
+    })
+}
 
-    let ss_s = positive_run("Software Security".to_string(), lt_ss, pre_trust);
-    let ss_ds = negative_run("Software Security".to_string(), ld_ss, ss_s);
-    let ss_final = negative_adjustment(ss_s, ss_ds);
+// New outgoing opinions of one peer, replacing its whole row of local trust or distrust
+pub type RowUpdate<T = f32> = (usize, Vec<(usize, T)>);
 
-    let sd_s = positive_run("Software Development".to_string(), lt_sd, pre_trust);
-    let sd_ds = negative_run("Software Development".to_string(), ld_sd, sd_s);
-    let _sd_final = negative_adjustment(sd_s, sd_ds);
+#[derive(Debug, Clone)]
+pub struct Update<T = f32> {
+    pub lt: CsrMatrix<T>,
+    pub ld: CsrMatrix<T>,
+    pub scores: Scores<T>,
+}
 
-    let snap1_trust: [f32; NUM_NEIGHBOURS] = [50., 0., 0., 50., 0.];
-    let snap1_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 50., 0., 0.];
+// Apply a few row updates to the matrices of a previous run and recompute its scores.
+// Trust warm-starts from the previous scores, and is kept as is when no trust row changed.
+// With one-hop distrust, it is only recomputed for peers distrusted by a changed row or by a
+// peer whose trust changed - every other entry is exactly what a full `run` would give.
+// Other distrust models are recomputed in full.
+pub fn update_run<T: Scalar>(
+    lt: &CsrMatrix<T>,
+    ld: &CsrMatrix<T>,
+    pre_trust: &[T],
+    previous: &Scores<T>,
+    lt_updates: &[RowUpdate<T>],
+    ld_updates: &[RowUpdate<T>],
+    config: &EigenTrustConfig,
+) -> Result<Update<T>, EigenTrustError> {
+    config.validate()?;
+    check_size("local distrust", lt.size(), ld.size())?;
+    check_size("pre-trust", lt.size(), pre_trust.len())?;
+    for previous in [&previous.trust, &previous.distrust] {
+        if previous.len() != lt.size() {
+            return Err(EigenTrustError::SeedLength {
+                expected: lt.size(),
+                found: previous.len(),
+            });
+        }
+    }
+    let mut violations = update_violations(lt_updates, lt.size(), Opinion::Trust);
+    violations.extend(update_violations(ld_updates, ld.size(), Opinion::Distrust));
+    ValidationError::check(violations)?;
+
+    let new_lt = lt.replace_rows(lt_updates);
+    let new_ld = ld.replace_rows(ld_updates);
+    let mut violations = local_violations(&new_lt, Opinion::Trust);
+    violations.extend(local_violations(&new_ld, Opinion::Distrust));
+    violations.extend(overlap_violations(&new_lt, &new_ld));
+    ValidationError::check(violations)?;
+
+    let (trust, report) = if lt_updates.is_empty() {
+        (previous.trust.clone(), previous.report)
+    } else {
+        positive_run(&new_lt, pre_trust, Some(&previous.trust), config)?
+    };
 
-    let snap2_trust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 0., 50.];
-    let snap2_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 50., 50., 0.];
+    if config.distrust != DistrustModel::OneHop {
+        let (distrust, distrust_report) = propagate_distrust(&new_lt, &new_ld, &trust, config)?;
+        let adjusted = adjust(&trust, &distrust, config.adjustment);
+        return Ok(Update {
+            lt: new_lt,
+            ld: new_ld,
+            scores: Scores {
+                trust,
+                distrust,
+                adjusted,
+                adjustment: config.adjustment,
+                report,
+                distrust_report,
+            },
+        });
+    }
 
-    let (snap1_score, confidence1, state1) =
-        calculate_snap_score_with_threshold(snap1_trust, snap1_distrust, ss_final, snap_threshold);
-    let (snap2_score, confidence2, state2) =
-        calculate_snap_score_with_threshold(snap2_trust, snap2_distrust, ss_final, snap_threshold);
+    // Rows whose normalised distrust or whose peer's trust changed, old and new targets alike
+    let mut sources: BTreeSet<usize> = ld_updates.iter().map(|(i, _)| *i).collect();
+    sources.extend((0..trust.len()).filter(|i| trust[*i] != previous.trust[*i]));
+    let mut affected = BTreeSet::new();
+    for i in sources {
+        affected.extend(ld.row(i).map(|(j, _)| j));
+        affected.extend(new_ld.row(i).map(|(j, _)| j));
+    }
 
-    println!();
-    println!(
-        "snap1(malicious) score: {}, confidence: {}, state: {:?}",
-        snap1_score, confidence1, state1
-    );
-    println!(
-        "snap2(secure) score: {}, confidence: {}, state: {:?}",
-        snap2_score, confidence2, state2
-    );
+    let ld_t = new_ld.normalise_rows().transpose();
+    let mut distrust = previous.distrust.clone();
+    for i in affected {
+        distrust[i] = ld_t.row_dot(i, &trust);
+    }
+    let adjusted = adjust(&trust, &distrust, config.adjustment);
+
+    Ok(Update {
+        lt: new_lt,
+        ld: new_ld,
+        scores: Scores {
+            trust,
+            distrust,
+            adjusted,
+            adjustment: config.adjustment,
+            report,
+            distrust_report: None,
+        },
+    })
+}
+
+// Iterate from `seed` (e.g. the previous run's scores), or from the pre-trust when there is none.
+// With a pre-trust distribution and `pre_trust_weight > 0` every step is a contraction, so the
+// fixed point is unique and doesn't depend on the seed - a warm start only changes how many
+// iterations it takes. Once converged, results from different seeds agree to within the tolerance.
+pub fn positive_run<T: Scalar>(
+    lt: &CsrMatrix<T>,
+    pre_trust: &[T],
+    seed: Option<&[T]>,
+    config: &EigenTrustConfig,
+) -> Result<(Vector<T>, ConvergenceReport), EigenTrustError> {
+    config.validate()?;
+    validate_lt(lt)?;
+    check_size("pre-trust", lt.size(), pre_trust.len())?;
+    let seed = seed.unwrap_or(pre_trust);
+    if seed.len() != pre_trust.len() {
+        return Err(EigenTrustError::SeedLength {
+            expected: pre_trust.len(),
+            found: seed.len(),
+        });
+    }
+    Ok(iterate_trust(lt, pre_trust, seed, config))
 }
 
-fn sybil_case() {
-    let pre_trust: [f32; NUM_NEIGHBOURS] = [0.0, 0.0, 0.0, 0.7, 0.3];
-    let snap_threshold = calculate_snap_score_threshold(pre_trust);
-    println!("security_threshold: {}", snap_threshold);
+// The iteration behind `positive_run`, once the inputs are checked
+fn iterate_trust<T: Scalar>(
+    lt: &CsrMatrix<T>,
+    pre_trust: &[T],
+    seed: &[T],
+    config: &EigenTrustConfig,
+) -> (Vector<T>, ConvergenceReport) {
+    // Incoming weights - row `i` holds the normalised trust other peers give to peer `i`.
+    // Peers who trust nobody spread their score over the pre-trust instead.
+    let lt_t = lt.normalise_rows().transpose();
+    let dangling = lt.empty_rows();
+
+    let mut s = seed.to_vec();
+    let convergence = config.convergence();
+    let pre_trust_weight: T = cast(config.pre_trust_weight);
+    let pre_trusted_scores = vec_scalar_mul(pre_trust, pre_trust_weight);
+
+    let mut report = ConvergenceReport::default();
+    for iteration in 1..=convergence.max_iter {
+        // Compute sum of incoming weights
+        let new_s = lt_t.mul_vec_with(&s, config.execution);
+        let dangling_mass: T = dangling.iter().map(|i| s[*i]).sum();
+        let new_s = vec_add(&new_s, &vec_scalar_mul(pre_trust, dangling_mass));
 
-    let lt_ss: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 10.0, 10.0, 0.0, 0.0], // - Peer 0 opinions
-        [10.0, 0.0, 10.0, 0.0, 0.0], // - Peer 1 opinions
-        [10.0, 10.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 3 opinions
-        [0.0, 10., 0.0, 0.0, 0.0],   // = Peer 4 opinions
-    ];
-    let ld_ss: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 0.0, 10.0, 0.0],   // - Peer 0 opinions
-        [0.0, 0.0, 0.0, 10.0, 0.0],   // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 10.0, 0.0],   // - Peer 2 opinions
-        [10.0, 10.0, 10.0, 0.0, 0.0], // - Peer 3 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],    // = Peer 4 opinions
-    ];
-    validate_lt_overlap(lt_ss, ld_ss);
+        let global_scores = vec_scalar_mul(&new_s, T::one() - pre_trust_weight);
+        let current_s = vec_add(&pre_trusted_scores, &global_scores);
+
+        report = convergence.check(iteration, &s, &current_s);
+        s = current_s;
+        if report.converged {
+            break;
+        }
+    }
 
-    let ss_s = positive_run("Software Security".to_string(), lt_ss, pre_trust);
-    let ss_ds = negative_run("Software Security".to_string(), ld_ss, ss_s);
-    let ss_final = negative_adjustment(ss_s, ss_ds);
+    (s, report)
+}
 
-    let snap1_trust: [f32; NUM_NEIGHBOURS] = [50., 50., 50., 0., 0.];
-    let snap1_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 50., 50.];
+pub fn negative_run<T: Scalar>(ld: &CsrMatrix<T>, s: &[T]) -> Result<Vector<T>, ValidationError> {
+    validate_ld(ld)?;
+    let ld_t = ld.normalise_rows().transpose();
 
-    let snap2_trust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 50., 50.];
-    let snap2_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 0., 0.];
+    // Compute sum of incoming weights
+    Ok(ld_t.mul_vec(s))
+}
 
-    let (snap1_score, confidence1, state1) =
-        calculate_snap_score_with_threshold(snap1_trust, snap1_distrust, ss_final, snap_threshold);
-    let (snap2_score, confidence2, state2) =
-        calculate_snap_score_with_threshold(snap2_trust, snap2_distrust, ss_final, snap_threshold);
+// Distrust under the configured model - one hop is the same as `negative_run`
+pub fn propagate_distrust<T: Scalar>(
+    lt: &CsrMatrix<T>,
+    ld: &CsrMatrix<T>,
+    s: &[T],
+    config: &EigenTrustConfig,
+) -> Result<(Vector<T>, Option<ConvergenceReport>), EigenTrustError> {
+    config.validate()?;
+    match config.distrust {
+        DistrustModel::OneHop => Ok((negative_run(ld, s)?, None)),
+        DistrustModel::KHop { hops, decay } => {
+            validate_lt(lt)?;
+            validate_ld(ld)?;
+            Ok((k_hop_distrust(lt, ld, s, hops, cast(decay), config), None))
+        }
+        DistrustModel::Iterative => {
+            validate_ld(ld)?;
+            let (distrust, report) = iterative_distrust(ld, s, config);
+            Ok((distrust, Some(report)))
+        }
+    }
+}
 
-    println!();
-    println!(
-        "snap1(malicious) score: {}, confidence: {}, state: {:?}",
-        snap1_score, confidence1, state1
-    );
-    println!(
-        "snap2(secure) score: {}, confidence: {}, state: {:?}",
-        snap2_score, confidence2, state2
-    );
+// sd = D^T s + decay * C^T D^T s + decay^2 * (C^T)^2 D^T s + ... over `hops` terms. Distrust
+// follows trust without the pre-trust fallback, so peers who trust nobody pass nothing on.
+fn k_hop_distrust<T: Scalar>(
+    lt: &CsrMatrix<T>,
+    ld: &CsrMatrix<T>,
+    s: &[T],
+    hops: usize,
+    decay: T,
+    config: &EigenTrustConfig,
+) -> Vector<T> {
+    let lt_t = lt.normalise_rows().transpose();
+    let ld_t = ld.normalise_rows().transpose();
+
+    let mut distrust = vec![T::zero(); s.len()];
+    let mut hop = s.to_vec();
+    let mut weight = T::one();
+    for h in 0..hops {
+        hop = if h == 0 {
+            ld_t.mul_vec_with(&hop, config.execution)
+        } else {
+            lt_t.mul_vec_with(&hop, config.execution)
+        };
+        distrust = vec_add(&distrust, &vec_scalar_mul(&hop, weight));
+        weight *= decay;
+    }
+    distrust
 }
 
-fn sleeping_agent_case() {
-    let pre_trust: [f32; NUM_NEIGHBOURS] = [0.0, 0.0, 0.0, 0.7, 0.3];
-    let snap_threshold = calculate_snap_score_threshold(pre_trust);
-    println!("security_threshold: {}", snap_threshold);
+// Feed the adjusted scores back as the weights of the next round of distrust
+fn iterative_distrust<T: Scalar>(
+    ld: &CsrMatrix<T>,
+    s: &[T],
+    config: &EigenTrustConfig,
+) -> (Vector<T>, ConvergenceReport) {
+    let ld_t = ld.normalise_rows().transpose();
+    let convergence = config.convergence();
+
+    let mut adjusted = s.to_vec();
+    let mut distrust = vec![T::zero(); s.len()];
+    let mut report = ConvergenceReport::default();
+    for iteration in 1..=convergence.max_iter {
+        distrust = ld_t.mul_vec_with(&clamp_zero(&adjusted), config.execution);
+        let current = negative_adjustment(s, &distrust);
+
+        report = convergence.check(iteration, &adjusted, &current);
+        adjusted = current;
+        if report.converged {
+            break;
+        }
+    }
+    (distrust, report)
+}
 
-    let lt_ss: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 0 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 2 opinions
-        [0.0, 0.0, 10.0, 0.0, 10.0], // - Peer 3 opinions
-        [0.0, 0.0, 10.0, 10.0, 0.0], // = Peer 4 opinions
-    ];
-    let ld_ss: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 0 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 2 opinions
-        [10.0, 10.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
-        [10.0, 10.0, 0.0, 0.0, 0.0], // = Peer 4 opinions
-    ];
-    validate_lt_overlap(lt_ss, ld_ss);
+pub fn adjust<T: Scalar>(s: &[T], sd: &[T], policy: AdjustmentPolicy) -> Vector<T> {
+    match policy {
+        AdjustmentPolicy::AllowNegative => negative_adjustment(s, sd),
+        AdjustmentPolicy::ClampZero => clamp_zero(&negative_adjustment(s, sd)),
+        AdjustmentPolicy::ClampRenormalise => {
+            normalise(&clamp_zero(&negative_adjustment(s, sd)), Norm::L1, None)
+        }
+        AdjustmentPolicy::Multiplicative => s
+            .iter()
+            .zip(sd)
+            .map(|(x, y)| {
+                // No trust and no distrust - nothing to scale
+                if *x + *y <= T::zero() {
+                    return T::zero();
+                }
+                *x * (T::one() - *y / (*x + *y))
+            })
+            .collect(),
+    }
+}
+
+fn clamp_zero<T: Scalar>(v: &[T]) -> Vector<T> {
+    v.iter().map(|x| x.max(T::zero())).collect()
+}
+
+pub fn negative_adjustment<T: Scalar>(s: &[T], sd: &[T]) -> Vector<T> {
+    let mut adjusted = vec![T::zero(); s.len()];
+    for i in 0..s.len() {
+        adjusted[i] = s[i] - sd[i];
+    }
 
-    let ss_s = positive_run("Software Security".to_string(), lt_ss, pre_trust);
-    let ssd_s = negative_run("Software Security".to_string(), ld_ss, ss_s);
-    let ssa_s = negative_adjustment(ss_s, ssd_s);
+    adjusted
+}
 
-    let snap1_trust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 0., 0.];
-    let snap1_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 0., 0.];
+// Scores of every snap, then the snaps whose state changed since the previous round
+fn print_snaps(
+    snaps: &SnapRegistry,
+    s: &[f32],
+    thresholds: &Thresholds,
+    history: &mut SnapHistory,
+) {
+    let scores = snaps.score(s, thresholds, Execution::Sequential);
+    for snap in &scores {
+        println!(
+            "{} score: {}, confidence: {}, state: {:?}",
+            snap.id, snap.score, snap.confidence, snap.state
+        );
+    }
+    for transition in history.record(&scores) {
+        println!("{}", transition);
+    }
+}
 
-    let snap2_trust: [f32; NUM_NEIGHBOURS] = [0., 0., 50., 50., 50.];
-    let snap2_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 0., 0.];
+fn print_scores(domain: &str, scores: &Scores) {
+    println!();
+    println!("{} - Trust:", domain);
+    println!("end: [{}]", format_vec(&scores.trust));
+    println!("{}", scores.report);
+
+    println!();
+    println!("{} - Distrust:", domain);
+    println!("end: [{}]", format_vec(&scores.distrust));
+    if let Some(report) = &scores.distrust_report {
+        println!("{}", report);
+    }
+
+    println!();
+    println!("Adjusted");
+    println!("adjusted: [{}]", format_vec(&scores.adjusted));
+}
+
+pub fn functional_case() {
+    let pre_trust: Vector = vec![0.0, 0.0, 0.0, 0.7, 0.3];
+    let config = EigenTrustConfig::default();
+    let thresholds = SnapConfig::default().thresholds(&pre_trust);
+    println!("snap thresholds: {}", thresholds);
+
+    let lt_ss = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 1.0, 0.0, 0.0], // - Peer 0 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
+        vec![11., 0.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
+        vec![0.0, 10., 0.0, 0.0, 0.0], // = Peer 4 opinions
+    ]);
+    let ld_ss = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 0 opinions
+        vec![0.0, 0.0, 10., 0.0, 0.0], // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
+        vec![0.0, 1.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
+        vec![10., 0.0, 0.0, 0.0, 0.0], // = Peer 4 opinions
+    ]);
+    let lt_sd = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 1.0, 0.0, 0.0], // - Peer 0 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
+        vec![1.0, 0.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // = Peer 4 opinions
+    ]);
+    let ld_sd = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 0 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
+        vec![0.0, 1.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0], // = Peer 4 opinions
+    ]);
+
+    let domains = [
+        Domain::new("Software Security", lt_ss, ld_ss, pre_trust.clone()),
+        Domain::new("Software Development", lt_sd, ld_sd, pre_trust.clone()),
+    ];
+    let scores = run_domains(&domains, &config).unwrap();
+    for domain in &domains {
+        print_scores(&domain.name, &scores[&domain.name]);
+    }
+    let ss_final = &scores["Software Security"].adjusted;
+
+    let snap1 = Ledger::from_attestations(vec![
+        Attestation::endorse(0, 1.),
+        Attestation::report(2, 1.),
+        Attestation::endorse(3, 1.),
+    ])
+    .unwrap();
+
+    let snap2 = Ledger::from_attestations(vec![
+        Attestation::report(2, 1.),
+        Attestation::report(3, 1.),
+        Attestation::endorse(4, 1.),
+    ])
+    .unwrap();
 
     let (snap1_score, confidence1, state1) =
-        calculate_snap_score_with_threshold(snap1_trust, snap1_distrust, ssa_s, snap_threshold);
+        calculate_snap_score_with_threshold(&snap1, ss_final, &thresholds);
     let (snap2_score, confidence2, state2) =
-        calculate_snap_score_with_threshold(snap2_trust, snap2_distrust, ssa_s, snap_threshold);
+        calculate_snap_score_with_threshold(&snap2, ss_final, &thresholds);
 
     println!();
-    println!("1st Round");
     println!(
         "snap1(malicious) score: {}, confidence: {}, state: {:?}",
         snap1_score, confidence1, state1
@@ -410,19 +985,62 @@ fn sleeping_agent_case() {
         snap2_score, confidence2, state2
     );
 
-    let snap1_trust: [f32; NUM_NEIGHBOURS] = [0., 0., 50., 0., 0.];
-    let snap1_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 0., 0.];
+    let beta = SnapConfig::default().beta;
+    for (name, snap) in [("snap1(malicious)", &snap1), ("snap2(secure)", &snap2)] {
+        let score = calculate_beta_snap_score(snap, ss_final, &beta).unwrap();
+        println!(
+            "{} beta mean: {}, interval: [{}, {}], state: {:?}",
+            name, score.mean, score.lower, score.upper, score.state
+        );
+    }
+}
 
-    let snap2_trust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 50., 50.];
-    let snap2_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 50., 0., 0.];
+pub fn sybil_case() {
+    let pre_trust: Vector = vec![0.0, 0.0, 0.0, 0.7, 0.3];
+    let config = EigenTrustConfig::default();
+    let thresholds = SnapConfig::default().thresholds(&pre_trust);
+    println!("snap thresholds: {}", thresholds);
+
+    let lt_ss = CsrMatrix::from_dense(&[
+        vec![0.0, 10.0, 10.0, 0.0, 0.0], // - Peer 0 opinions
+        vec![10.0, 0.0, 10.0, 0.0, 0.0], // - Peer 1 opinions
+        vec![10.0, 10.0, 0.0, 0.0, 0.0], // - Peer 2 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 3 opinions
+        vec![0.0, 10., 0.0, 0.0, 0.0],   // = Peer 4 opinions
+    ]);
+    let ld_ss = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 0.0, 10.0, 0.0],   // - Peer 0 opinions
+        vec![0.0, 0.0, 0.0, 10.0, 0.0],   // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 10.0, 0.0],   // - Peer 2 opinions
+        vec![10.0, 10.0, 10.0, 0.0, 0.0], // - Peer 3 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],    // = Peer 4 opinions
+    ]);
+
+    let ss = run(&lt_ss, &ld_ss, &pre_trust, &config).unwrap();
+    print_scores("Software Security", &ss);
+    let ss_final = ss.adjusted;
+
+    let snap1 = Ledger::from_attestations(vec![
+        Attestation::endorse(0, 1.),
+        Attestation::endorse(1, 1.),
+        Attestation::endorse(2, 1.),
+        Attestation::report(3, 1.),
+        Attestation::report(4, 1.),
+    ])
+    .unwrap();
+
+    let snap2 = Ledger::from_attestations(vec![
+        Attestation::endorse(3, 1.),
+        Attestation::endorse(4, 1.),
+    ])
+    .unwrap();
 
     let (snap1_score, confidence1, state1) =
-        calculate_snap_score_with_threshold(snap1_trust, snap1_distrust, ssa_s, snap_threshold);
+        calculate_snap_score_with_threshold(&snap1, &ss_final, &thresholds);
     let (snap2_score, confidence2, state2) =
-        calculate_snap_score_with_threshold(snap2_trust, snap2_distrust, ssa_s, snap_threshold);
+        calculate_snap_score_with_threshold(&snap2, &ss_final, &thresholds);
 
     println!();
-    println!("2nd Round");
     println!(
         "snap1(malicious) score: {}, confidence: {}, state: {:?}",
         snap1_score, confidence1, state1
@@ -432,46 +1050,414 @@ fn sleeping_agent_case() {
         snap2_score, confidence2, state2
     );
 
-    let lt_ss: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 0.0, 0.0, 0.0],  // - Peer 0 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],  // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],  // - Peer 2 opinions
-        [0.0, 0.0, 0.0, 0.0, 10.0], // - Peer 3 opinions
-        [0.0, 0.0, 0.0, 10.0, 0.0], // = Peer 4 opinions
-    ];
-    let ld_ss: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0.0, 0.0, 0.0, 0.0, 0.0],    // - Peer 0 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],    // - Peer 1 opinions
-        [0.0, 0.0, 0.0, 0.0, 0.0],    // - Peer 2 opinions
-        [10.0, 10.0, 10.0, 0.0, 0.0], // - Peer 3 opinions
-        [10.0, 10.0, 10.0, 0.0, 0.0], // = Peer 4 opinions
-    ];
-    validate_lt_overlap(lt_ss, ld_ss);
+    let beta = SnapConfig::default().beta;
+    for (name, snap) in [("snap1(malicious)", &snap1), ("snap2(secure)", &snap2)] {
+        let score = calculate_beta_snap_score(snap, &ss_final, &beta).unwrap();
+        println!(
+            "{} beta mean: {}, interval: [{}, {}], state: {:?}",
+            name, score.mean, score.lower, score.upper, score.state
+        );
+    }
+}
 
-    let ss_s = positive_run("Software Security".to_string(), lt_ss, pre_trust);
-    let ssd_s = negative_run("Software Security".to_string(), ld_ss, ss_s);
-    let ssa_s = negative_adjustment(ss_s, ssd_s);
+pub fn sleeping_agent_case() {
+    let pre_trust: Vector = vec![0.0, 0.0, 0.0, 0.7, 0.3];
+    let config = EigenTrustConfig::default();
+    let thresholds = SnapConfig::default().thresholds(&pre_trust);
+    println!("snap thresholds: {}", thresholds);
+
+    let lt_ss = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 0 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 2 opinions
+        vec![0.0, 0.0, 10.0, 0.0, 10.0], // - Peer 3 opinions
+        vec![0.0, 0.0, 10.0, 10.0, 0.0], // = Peer 4 opinions
+    ]);
+    let ld_ss = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 0 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],   // - Peer 2 opinions
+        vec![10.0, 10.0, 0.0, 0.0, 0.0], // - Peer 3 opinions
+        vec![10.0, 10.0, 0.0, 0.0, 0.0], // = Peer 4 opinions
+    ]);
+
+    let ss = run(&lt_ss, &ld_ss, &pre_trust, &config).unwrap();
+    print_scores("Software Security", &ss);
+    let ssa_s = ss.adjusted;
+
+    // Attestations accumulate across rounds - a later vote of the same peer replaces its
+    // earlier one
+    let mut snaps = SnapRegistry::new();
+    let mut history = SnapHistory::new();
+    snaps.insert("snap1(malicious)", Ledger::new());
+    for peer in [2, 3, 4] {
+        snaps
+            .attest("snap2(secure)", Attestation::endorse(peer, 1.))
+            .unwrap();
+    }
 
-    let snap1_trust: [f32; NUM_NEIGHBOURS] = [0., 0., 50., 0., 0.];
-    let snap1_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 50., 50.];
+    println!();
+    println!("1st Round");
+    print_snaps(&snaps, &ssa_s, &thresholds, &mut history);
 
-    // TODO(ek): peer 2: what does he say?
-    let snap2_trust: [f32; NUM_NEIGHBOURS] = [0., 0., 0., 50., 50.];
-    let snap2_distrust: [f32; NUM_NEIGHBOURS] = [0., 0., 50., 0., 0.];
+    snaps
+        .attest("snap1(malicious)", Attestation::endorse(2, 1.))
+        .unwrap();
+    snaps
+        .attest("snap2(secure)", Attestation::report(2, 1.))
+        .unwrap();
 
-    let (snap1_score, confidence1, state1) =
-        calculate_snap_score_with_threshold(snap1_trust, snap1_distrust, ssa_s, snap_threshold);
-    let (snap2_score, confidence2, state2) =
-        calculate_snap_score_with_threshold(snap2_trust, snap2_distrust, ssa_s, snap_threshold);
+    println!();
+    println!("2nd Round");
+    print_snaps(&snaps, &ssa_s, &thresholds, &mut history);
+
+    let lt_ss = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],  // - Peer 0 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],  // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],  // - Peer 2 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 10.0], // - Peer 3 opinions
+        vec![0.0, 0.0, 0.0, 10.0, 0.0], // = Peer 4 opinions
+    ]);
+    let ld_ss = CsrMatrix::from_dense(&[
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],    // - Peer 0 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],    // - Peer 1 opinions
+        vec![0.0, 0.0, 0.0, 0.0, 0.0],    // - Peer 2 opinions
+        vec![10.0, 10.0, 10.0, 0.0, 0.0], // - Peer 3 opinions
+        vec![10.0, 10.0, 10.0, 0.0, 0.0], // = Peer 4 opinions
+    ]);
+
+    let ss = run(&lt_ss, &ld_ss, &pre_trust, &config).unwrap();
+    print_scores("Software Security", &ss);
+    let ssa_s = ss.adjusted;
+
+    for peer in [3, 4] {
+        snaps
+            .attest("snap1(malicious)", Attestation::report(peer, 1.))
+            .unwrap();
+    }
+    // TODO(ek): peer 2: what does he say?
 
     println!();
     println!("3rd Round");
-    println!(
-        "snap1(malicious) score: {}, confidence: {}, state: {:?}",
-        snap1_score, confidence1, state1
-    );
-    println!(
-        "snap2(secure) score: {}, confidence: {}, state: {:?}",
-        snap2_score, confidence2, state2
-    );
+    print_snaps(&snaps, &ssa_s, &thresholds, &mut history);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> (CsrMatrix<f64>, CsrMatrix<f64>, Vector<f64>) {
+        let lt = CsrMatrix::from_triplets(
+            4,
+            vec![
+                (0, 1, 0.6),
+                (0, 2, 0.4),
+                (1, 2, 1.),
+                (2, 0, 0.5),
+                (2, 3, 0.5),
+            ],
+        );
+        let ld = CsrMatrix::from_triplets(4, vec![(0, 3, 1.), (1, 3, 0.2)]);
+        (lt, ld, vec![0.5, 0.5, 0., 0.])
+    }
+
+    fn config() -> EigenTrustConfig {
+        EigenTrustConfig {
+            max_iter: 200,
+            tolerance: 1e-9,
+            ..EigenTrustConfig::default()
+        }
+    }
+
+    fn assert_close(a: &[f64], b: &[f64]) {
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-6, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn assert_matches_run(lt_updates: &[RowUpdate<f64>], ld_updates: &[RowUpdate<f64>]) {
+        let (lt, ld, pre_trust) = graph();
+        let config = config();
+        let previous = run(&lt, &ld, &pre_trust, &config).unwrap();
+        let update = update_run(
+            &lt, &ld, &pre_trust, &previous, lt_updates, ld_updates, &config,
+        )
+        .unwrap();
+        let fresh = run(&update.lt, &update.ld, &pre_trust, &config).unwrap();
+        assert_close(&update.scores.trust, &fresh.trust);
+        assert_close(&update.scores.distrust, &fresh.distrust);
+        assert_close(&update.scores.adjusted, &fresh.adjusted);
+    }
+
+    #[test]
+    fn update_matches_run_after_trust_change() {
+        assert_matches_run(&[(1, vec![(0, 0.3), (2, 0.7)])], &[]);
+    }
+
+    #[test]
+    fn update_matches_run_after_distrust_change() {
+        assert_matches_run(&[], &[(2, vec![(1, 1.)]), (0, vec![])]);
+    }
+
+    #[test]
+    fn update_rejects_unknown_peer() {
+        let (lt, ld, pre_trust) = graph();
+        let config = config();
+        let previous = run(&lt, &ld, &pre_trust, &config).unwrap();
+        let lt_updates = [(0, vec![(4, 1.)])];
+        let ld_updates = [(7, vec![])];
+        let error = update_run(
+            &lt,
+            &ld,
+            &pre_trust,
+            &previous,
+            &lt_updates,
+            &ld_updates,
+            &config,
+        )
+        .unwrap_err();
+        let EigenTrustError::Invalid(error) = error else {
+            panic!("expected a validation error, found {}", error);
+        };
+        let rules: Vec<Rule> = error.violations.iter().map(|v| v.rule).collect();
+        assert_eq!(rules, vec![Rule::OutOfRange { size: 4 }; 2]);
+    }
+
+    #[test]
+    fn seed_does_not_change_the_fixed_point() {
+        let (lt, _, pre_trust) = graph();
+        let config = config();
+        let (cold, _) = positive_run(&lt, &pre_trust, None, &config).unwrap();
+        let seed = vec![0.1, 0.2, 0.3, 0.4];
+        let (warm, report) = positive_run(&lt, &pre_trust, Some(&seed), &config).unwrap();
+        assert!(report.converged);
+        assert_close(&cold, &warm);
+
+        let error = positive_run(&lt, &pre_trust, Some(&seed[..3]), &config).unwrap_err();
+        assert_eq!(
+            error,
+            EigenTrustError::SeedLength {
+                expected: 4,
+                found: 3
+            }
+        );
+    }
+
+    #[test]
+    fn multiplicative_scales_by_the_share_of_trust() {
+        let adjusted = adjust(
+            &[0.3, 0.1, 0., 0.2],
+            &[0.1, 0.3, 0., 0.],
+            AdjustmentPolicy::Multiplicative,
+        );
+        assert_close(&adjusted, &[0.225, 0.025, 0., 0.2]);
+    }
+
+    #[test]
+    fn sizes_must_agree() {
+        let (lt, ld, pre_trust) = graph();
+        let config = config();
+        let error = run(&lt, &ld, &pre_trust[..2], &config).unwrap_err();
+        assert_eq!(
+            error,
+            EigenTrustError::Size {
+                name: "pre-trust",
+                expected: 4,
+                found: 2
+            }
+        );
+        let mut long = pre_trust.clone();
+        long.push(0.);
+        assert!(run(&lt, &ld, &long, &config).is_err());
+        assert!(positive_run(&lt, &long, None, &config).is_err());
+
+        let ld = CsrMatrix::from_triplets(3, vec![]);
+        assert!(matches!(
+            run(&lt, &ld, &pre_trust, &config),
+            Err(EigenTrustError::Size {
+                name: "local distrust",
+                ..
+            })
+        ));
+    }
+
+    fn entries(m: &CsrMatrix<f64>) -> Vec<(usize, usize, f64)> {
+        m.entries().collect()
+    }
+
+    #[test]
+    fn sanitise_repairs_each_rule() {
+        let lt = CsrMatrix::from_triplets(
+            3,
+            vec![(0, 1, f64::NAN), (0, 2, 0.5), (1, 1, 0.5), (2, 0, -0.5)],
+        );
+        let ld = CsrMatrix::from_triplets(3, vec![(1, 0, f64::INFINITY)]);
+        let (lt, ld, report) = sanitise(&lt, &ld, OverlapPolicy::Net);
+        assert_eq!(entries(&lt), vec![(0, 2, 0.5)]);
+        assert_eq!(entries(&ld), vec![]);
+
+        let repairs: Vec<_> = report
+            .repairs
+            .iter()
+            .map(|r| (r.opinion, r.from, r.to, r.after, r.rule))
+            .collect();
+        assert_eq!(
+            repairs,
+            vec![
+                (Opinion::Trust, 0, 1, 0., Rule::NonFinite),
+                (Opinion::Trust, 1, 1, 0., Rule::SelfTrust),
+                (Opinion::Trust, 2, 0, 0., Rule::Negative),
+                (Opinion::Distrust, 1, 0, 0., Rule::NonFinite),
+            ]
+        );
+        assert_eq!(report.repairs[1].before, 0.5);
+        assert_eq!(report.repairs[2].before, -0.5);
+    }
+
+    // Peer 0 trusts peer 1 more than it distrusts it, and peer 1 trusts and distrusts peer 2
+    // equally. Peer 2's distrust of peer 0 doesn't overlap and is never touched.
+    fn overlap(policy: OverlapPolicy) -> (CsrMatrix<f64>, CsrMatrix<f64>, SanitiseReport) {
+        let lt = CsrMatrix::from_triplets(3, vec![(0, 1, 0.75), (1, 2, 0.5)]);
+        let ld = CsrMatrix::from_triplets(3, vec![(0, 1, 0.25), (1, 2, 0.5), (2, 0, 1.)]);
+        sanitise(&lt, &ld, policy)
+    }
+
+    fn overlap_repairs(report: &SanitiseReport) -> Vec<(Opinion, usize, usize, f32, f32)> {
+        for repair in &report.repairs {
+            let distrust = if repair.from == 0 { 0.25 } else { 0.5 };
+            assert_eq!(repair.rule, Rule::TrustDistrustOverlap { distrust });
+        }
+        report
+            .repairs
+            .iter()
+            .map(|r| (r.opinion, r.from, r.to, r.before, r.after))
+            .collect()
+    }
+
+    #[test]
+    fn sanitise_net_keeps_the_difference() {
+        let (lt, ld, report) = overlap(OverlapPolicy::Net);
+        assert_eq!(entries(&lt), vec![(0, 1, 0.5)]);
+        assert_eq!(entries(&ld), vec![(2, 0, 1.)]);
+        assert_eq!(
+            overlap_repairs(&report),
+            vec![
+                (Opinion::Trust, 0, 1, 0.75, 0.5),
+                (Opinion::Distrust, 0, 1, 0.25, 0.),
+                (Opinion::Trust, 1, 2, 0.5, 0.),
+                (Opinion::Distrust, 1, 2, 0.5, 0.),
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitise_keep_larger_drops_ties() {
+        let (lt, ld, report) = overlap(OverlapPolicy::KeepLarger);
+        assert_eq!(entries(&lt), vec![(0, 1, 0.75)]);
+        assert_eq!(entries(&ld), vec![(2, 0, 1.)]);
+        assert_eq!(
+            overlap_repairs(&report),
+            vec![
+                (Opinion::Distrust, 0, 1, 0.25, 0.),
+                (Opinion::Trust, 1, 2, 0.5, 0.),
+                (Opinion::Distrust, 1, 2, 0.5, 0.),
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitise_drop_both() {
+        let (lt, ld, report) = overlap(OverlapPolicy::DropBoth);
+        assert_eq!(entries(&lt), vec![]);
+        assert_eq!(entries(&ld), vec![(2, 0, 1.)]);
+        assert_eq!(
+            overlap_repairs(&report),
+            vec![
+                (Opinion::Trust, 0, 1, 0.75, 0.),
+                (Opinion::Distrust, 0, 1, 0.25, 0.),
+                (Opinion::Trust, 1, 2, 0.5, 0.),
+                (Opinion::Distrust, 1, 2, 0.5, 0.),
+            ]
+        );
+    }
+
+    #[test]
+    fn f64_runs_use_f64_parameters() {
+        let lt = CsrMatrix::from_triplets(2, vec![(0, 1, 1.), (1, 0, 1.)]);
+        let config = EigenTrustConfig {
+            pre_trust_weight: 0.1,
+            max_iter: 1,
+            ..EigenTrustConfig::default()
+        };
+        let (trust, _) = positive_run(&lt, &[1f64, 0.], None, &config).unwrap();
+        assert_eq!(trust, vec![0.1, 1. - 0.1]);
+    }
+
+    fn distrust(model: DistrustModel) -> Scores<f64> {
+        let (lt, ld, pre_trust) = graph();
+        let config = EigenTrustConfig {
+            distrust: model,
+            ..config()
+        };
+        run(&lt, &ld, &pre_trust, &config).unwrap()
+    }
+
+    #[test]
+    fn one_k_hop_is_one_hop() {
+        let one_hop = distrust(DistrustModel::OneHop);
+        let k_hop = distrust(DistrustModel::KHop {
+            hops: 1,
+            decay: 0.5,
+        });
+        assert_eq!(one_hop.distrust, k_hop.distrust);
+    }
+
+    #[test]
+    fn second_hop_reaches_vouched_peers() {
+        // Peer 0 distrusts peer 3, who vouches for peer 4 and nobody else
+        let lt = CsrMatrix::from_triplets(5, vec![(0, 1, 1.), (1, 0, 1.), (3, 4, 1.)]);
+        let ld = CsrMatrix::from_triplets(5, vec![(0, 3, 1.)]);
+        let pre_trust = vec![0.5, 0.5, 0., 0., 0.];
+        let config = EigenTrustConfig {
+            distrust: DistrustModel::KHop {
+                hops: 2,
+                decay: 0.25,
+            },
+            ..config()
+        };
+        let scores = run(&lt, &ld, &pre_trust, &config).unwrap();
+        let sd = &scores.distrust;
+        assert_eq!(sd[3], scores.trust[0]);
+        assert_eq!(sd[4], 0.25 * sd[3]);
+        assert_eq!(&sd[..3], &[0., 0., 0.]);
+    }
+
+    #[test]
+    fn iterative_distrust_converges() {
+        let scores = distrust(DistrustModel::Iterative);
+        assert!(scores.distrust_report.unwrap().converged);
+        // At the fixed point, distrust is weighted by the adjusted scores it produces
+        let (_, ld, _) = graph();
+        let adjusted = negative_adjustment(&scores.trust, &scores.distrust);
+        let weighted = ld
+            .normalise_rows()
+            .transpose()
+            .mul_vec(&clamp_zero(&adjusted));
+        assert_close(&scores.distrust, &weighted);
+    }
+
+    #[test]
+    fn invalid_k_hop_is_rejected() {
+        let (lt, ld, pre_trust) = graph();
+        for (hops, decay) in [(0, 0.5), (2, 1.5), (2, f64::NAN)] {
+            let config = EigenTrustConfig {
+                distrust: DistrustModel::KHop { hops, decay },
+                ..config()
+            };
+            assert!(matches!(
+                run(&lt, &ld, &pre_trust, &config),
+                Err(EigenTrustError::Config(_))
+            ));
+        }
+    }
 }
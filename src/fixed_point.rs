@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::eigen_trust::{
+    local_violations, pre_trust_violations, Opinion, Rule, ValidationError, Violation,
+};
+use crate::sparse::CsrMatrix;
+use crate::utils::ConvergenceReport;
+
+// 1.0 in fixed-point units
+const SCALE: u64 = 1_000_000_000;
+const NUM_ITER: usize = 30;
+const TOLERANCE: f64 = 1e-6;
+const PRE_TRUST_WEIGHT: f64 = 0.5;
+
+// EigenTrust in integer arithmetic. Scores, pre-trust and weights are multiples of `1 / scale`
+// and every division rounds down, so the same inputs give the same scores on every platform.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FixedPointConfig {
+    pub scale: u64,
+    // Share of the pre-trust in every iteration, in [0, 1] - rounded to `1 / scale` in `run`
+    pub pre_trust_weight: f64,
+    pub max_iter: usize,
+    // Largest L1 residual between iterations, as a fraction like `pre_trust_weight`
+    pub tolerance: f64,
+}
+
+impl Default for FixedPointConfig {
+    fn default() -> Self {
+        Self {
+            scale: SCALE,
+            pre_trust_weight: PRE_TRUST_WEIGHT,
+            max_iter: NUM_ITER,
+            tolerance: TOLERANCE,
+        }
+    }
+}
+
+impl FixedPointConfig {
+    pub fn validate(&self) -> Result<(), FixedPointError> {
+        if self.scale == 0 {
+            return Err(FixedPointError::Config(
+                "scale must be positive".to_string(),
+            ));
+        }
+        if !(0. ..=1.).contains(&self.pre_trust_weight) {
+            return Err(FixedPointError::Config(format!(
+                "pre_trust_weight must be in [0, 1], found {}",
+                self.pre_trust_weight
+            )));
+        }
+        if !(self.tolerance >= 0. && self.tolerance.is_finite()) {
+            return Err(FixedPointError::Config(format!(
+                "tolerance must be a non-negative number, found {}",
+                self.tolerance
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FixedPointError {
+    Config(String),
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for FixedPointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixedPointError::Config(message) => {
+                write!(f, "invalid fixed_point config: {}", message)
+            }
+            FixedPointError::Invalid(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for FixedPointError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FixedPointError::Config(_) => None,
+            FixedPointError::Invalid(error) => Some(error),
+        }
+    }
+}
+
+impl From<ValidationError> for FixedPointError {
+    fn from(error: ValidationError) -> Self {
+        FixedPointError::Invalid(error)
+    }
+}
+
+// A `(from, to, value)` cell of local trust or distrust
+pub type Entry = (usize, usize, u64);
+
+#[derive(Debug, Clone)]
+pub struct FixedScores {
+    pub trust: Vec<u64>,
+    pub distrust: Vec<u64>,
+    pub adjusted: Vec<i64>,
+    // The residual is converted to a fraction for display only
+    pub report: ConvergenceReport,
+}
+
+// Round to the nearest multiple of `1 / scale`. Negative values and NaN become 0 and anything too
+// large saturates at `u64::MAX`, so check the inputs first - see `to_fixed_matrices`.
+pub fn to_fixed(x: f64, scale: u64) -> u64 {
+    (x * scale as f64).round() as u64
+}
+
+pub fn from_fixed(x: u64, scale: u64) -> f64 {
+    x as f64 / scale as f64
+}
+
+pub fn to_fixed_vec(v: &[f64], scale: u64) -> Vec<u64> {
+    v.iter().map(|x| to_fixed(*x, scale)).collect()
+}
+
+// Pre-trust in fixed point, rejecting negative and non-finite values like `to_fixed_matrices`
+pub fn to_fixed_pre_trust(pre_trust: &[f64], scale: u64) -> Result<Vec<u64>, FixedPointError> {
+    ValidationError::check(pre_trust_violations(pre_trust))?;
+    Ok(to_fixed_vec(pre_trust, scale))
+}
+
+pub fn to_fixed_entries(m: &CsrMatrix<f64>, scale: u64) -> Vec<Entry> {
+    m.entries()
+        .map(|(i, j, v)| (i, j, to_fixed(v, scale)))
+        .collect()
+}
+
+// Local trust and distrust in fixed point, after the same cell checks as the float path, so
+// negative and non-finite cells are rejected rather than rounded to 0 or saturated
+pub fn to_fixed_matrices(
+    lt: &CsrMatrix<f64>,
+    ld: &CsrMatrix<f64>,
+    scale: u64,
+) -> Result<(Vec<Entry>, Vec<Entry>), ValidationError> {
+    let mut violations = local_violations(lt, Opinion::Trust);
+    violations.extend(local_violations(ld, Opinion::Distrust));
+    ValidationError::check(violations)?;
+    Ok((to_fixed_entries(lt, scale), to_fixed_entries(ld, scale)))
+}
+
+// Entries naming a peer outside the `size` peers of the pre-trust or scores
+fn range_violations(
+    entries: &[Entry],
+    size: usize,
+    opinion: Opinion,
+    scale: u64,
+) -> Vec<Violation> {
+    entries
+        .iter()
+        .filter(|(i, j, _)| *i >= size || *j >= size)
+        .map(|(i, j, value)| Violation {
+            opinion,
+            from: *i,
+            to: *j,
+            value: from_fixed(*value, scale) as f32,
+            rule: Rule::OutOfRange { size },
+        })
+        .collect()
+}
+
+// Violations report values as fractions, the same as the float path
+fn self_trust_violations(entries: &[Entry], opinion: Opinion, scale: u64) -> Vec<Violation> {
+    entries
+        .iter()
+        .filter(|(i, j, _)| i == j)
+        .map(|(i, j, value)| Violation {
+            opinion,
+            from: *i,
+            to: *j,
+            value: from_fixed(*value, scale) as f32,
+            rule: Rule::SelfTrust,
+        })
+        .collect()
+}
+
+fn overlap_violations(lt: &[Entry], ld: &[Entry], scale: u64) -> Vec<Violation> {
+    let mut distrust = HashMap::new();
+    for (i, j, v) in ld {
+        *distrust.entry((*i, *j)).or_insert(0) += *v;
+    }
+    let mut violations = Vec::new();
+    for (i, j, value) in lt {
+        match distrust.get(&(*i, *j)) {
+            Some(d) if *d != 0 && *value != 0 => violations.push(Violation {
+                opinion: Opinion::Trust,
+                from: *i,
+                to: *j,
+                value: from_fixed(*value, scale) as f32,
+                rule: Rule::TrustDistrustOverlap {
+                    distrust: from_fixed(*d, scale) as f32,
+                },
+            }),
+            _ => {}
+        }
+    }
+    violations
+}
+
+// Incoming view of a matrix, where row `i` holds the weight each peer gives to peer `i`
+struct Incoming {
+    rows: Vec<Vec<(usize, u64)>>,
+    // Peers without any outgoing weight
+    empty: Vec<usize>,
+}
+
+// Normalise every row to sum to `scale` (rounding down) - rows summing to zero stay empty
+fn incoming(size: usize, entries: &[(usize, usize, u64)], scale: u64) -> Incoming {
+    let mut rows = vec![Vec::new(); size];
+    for (i, j, v) in entries {
+        if *v != 0 {
+            rows[*i].push((*j, *v));
+        }
+    }
+
+    let mut incoming = vec![Vec::new(); size];
+    let mut empty = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        let sum: u128 = row.iter().map(|(_, v)| *v as u128).sum();
+        if sum == 0 {
+            empty.push(i);
+            continue;
+        }
+        for (j, v) in row {
+            incoming[*j].push((i, (*v as u128 * scale as u128 / sum) as u64));
+        }
+    }
+    Incoming {
+        rows: incoming,
+        empty,
+    }
+}
+
+// Integer sums are exact, so the order of the incoming weights doesn't matter. The scores of
+// peers without outgoing weight are spread over the `fallback`, if any.
+fn mul_vec(incoming: &Incoming, s: &[u64], fallback: Option<&[u64]>, scale: u64) -> Vec<u64> {
+    let dangling: u128 = incoming.empty.iter().map(|i| s[*i] as u128).sum();
+    incoming
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut sum: u128 = row.iter().map(|(j, w)| *w as u128 * s[*j] as u128).sum();
+            if let Some(fallback) = fallback {
+                sum += fallback[i] as u128 * dangling;
+            }
+            (sum / scale as u128) as u64
+        })
+        .collect()
+}
+
+pub fn run(
+    lt: &[(usize, usize, u64)],
+    ld: &[(usize, usize, u64)],
+    pre_trust: &[u64],
+    config: &FixedPointConfig,
+) -> Result<FixedScores, FixedPointError> {
+    config.validate()?;
+    let mut violations = range_violations(lt, pre_trust.len(), Opinion::Trust, config.scale);
+    violations.extend(range_violations(
+        ld,
+        pre_trust.len(),
+        Opinion::Distrust,
+        config.scale,
+    ));
+    ValidationError::check(violations)?;
+    let mut violations = self_trust_violations(lt, Opinion::Trust, config.scale);
+    violations.extend(self_trust_violations(ld, Opinion::Distrust, config.scale));
+    violations.extend(overlap_violations(lt, ld, config.scale));
+    ValidationError::check(violations)?;
+
+    let (trust, report) = positive_run(lt, pre_trust, config)?;
+    let distrust = negative_run(ld, &trust, config)?;
+    let adjusted = negative_adjustment(&trust, &distrust);
+
+    Ok(FixedScores {
+        trust,
+        distrust,
+        adjusted,
+        report,
+    })
+}
+
+pub fn positive_run(
+    lt: &[(usize, usize, u64)],
+    pre_trust: &[u64],
+    config: &FixedPointConfig,
+) -> Result<(Vec<u64>, ConvergenceReport), FixedPointError> {
+    config.validate()?;
+    ValidationError::check(range_violations(
+        lt,
+        pre_trust.len(),
+        Opinion::Trust,
+        config.scale,
+    ))?;
+    ValidationError::check(self_trust_violations(lt, Opinion::Trust, config.scale))?;
+    let scale = config.scale as u128;
+    let pre_trust_weight = to_fixed(config.pre_trust_weight, config.scale) as u128;
+    let tolerance = to_fixed(config.tolerance, config.scale);
+    let lt_t = incoming(pre_trust.len(), lt, config.scale);
+
+    let mut s = pre_trust.to_vec();
+    let mut report = ConvergenceReport::default();
+    for iteration in 1..=config.max_iter {
+        // Compute sum of incoming weights
+        let new_s = mul_vec(&lt_t, &s, Some(pre_trust), config.scale);
+
+        let current_s: Vec<u64> = pre_trust
+            .iter()
+            .zip(&new_s)
+            .map(|(p, x)| {
+                let score = pre_trust_weight * *p as u128 + (scale - pre_trust_weight) * *x as u128;
+                (score / scale) as u64
+            })
+            .collect();
+
+        let residual: u64 = s.iter().zip(&current_s).map(|(a, b)| a.abs_diff(*b)).sum();
+        report = ConvergenceReport {
+            iterations: iteration,
+            residual: from_fixed(residual, config.scale) as f32,
+            converged: residual <= tolerance,
+        };
+        s = current_s;
+        if report.converged {
+            break;
+        }
+    }
+
+    Ok((s, report))
+}
+
+pub fn negative_run(
+    ld: &[(usize, usize, u64)],
+    s: &[u64],
+    config: &FixedPointConfig,
+) -> Result<Vec<u64>, FixedPointError> {
+    config.validate()?;
+    ValidationError::check(range_violations(
+        ld,
+        s.len(),
+        Opinion::Distrust,
+        config.scale,
+    ))?;
+    ValidationError::check(self_trust_violations(ld, Opinion::Distrust, config.scale))?;
+    let ld_t = incoming(s.len(), ld, config.scale);
+
+    // Compute sum of incoming weights
+    Ok(mul_vec(&ld_t, s, None, config.scale))
+}
+
+pub fn negative_adjustment(s: &[u64], sd: &[u64]) -> Vec<i64> {
+    s.iter()
+        .zip(sd)
+        .map(|(x, y)| *x as i64 - *y as i64)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eigen_trust::{self, EigenTrustConfig};
+
+    fn graph() -> (Vec<Entry>, Vec<Entry>, Vec<u64>) {
+        let lt = vec![
+            (0, 1, 600),
+            (0, 2, 400),
+            (1, 2, 1000),
+            (2, 0, 500),
+            (2, 3, 500),
+        ];
+        let ld = vec![(0, 3, 1000), (1, 3, 200)];
+        (lt, ld, vec![500, 500, 0, 0])
+    }
+
+    fn config() -> FixedPointConfig {
+        FixedPointConfig {
+            scale: 1000,
+            ..FixedPointConfig::default()
+        }
+    }
+
+    #[test]
+    fn scores_are_pinned() {
+        let (lt, ld, pre_trust) = graph();
+        let scores = run(&lt, &ld, &pre_trust, &config()).unwrap();
+        assert_eq!(scores.trust, vec![326, 363, 246, 61]);
+        assert_eq!(scores.distrust, vec![0, 0, 0, 689]);
+        assert_eq!(scores.adjusted, vec![326, 363, 246, -628]);
+        assert_eq!(scores.report.iterations, 8);
+        assert!(scores.report.converged);
+    }
+
+    #[test]
+    fn scores_agree_with_f64() {
+        let (lt, ld, pre_trust) = graph();
+        let size = pre_trust.len();
+        let config = FixedPointConfig {
+            tolerance: 0.,
+            ..FixedPointConfig::default()
+        };
+        let scale = config.scale;
+        // Rescale the fixture from units of 1/1000 to the default scale
+        let rescale = |entries: &[Entry]| -> Vec<Entry> {
+            entries
+                .iter()
+                .map(|(i, j, v)| (*i, *j, v * scale / 1000))
+                .collect()
+        };
+        let pre_trust: Vec<u64> = pre_trust.iter().map(|v| v * scale / 1000).collect();
+        let (lt, ld) = (rescale(&lt), rescale(&ld));
+        let scores = run(&lt, &ld, &pre_trust, &config).unwrap();
+
+        let to_float = |entries: &[Entry]| {
+            let entries = entries
+                .iter()
+                .map(|(i, j, v)| (*i, *j, from_fixed(*v, scale)))
+                .collect();
+            CsrMatrix::from_triplets(size, entries)
+        };
+        let float_pre_trust: Vec<f64> = pre_trust.iter().map(|v| from_fixed(*v, scale)).collect();
+        let float_config = EigenTrustConfig {
+            tolerance: 0.,
+            max_iter: config.max_iter,
+            ..Default::default()
+        };
+        let float = eigen_trust::run(
+            &to_float(&lt),
+            &to_float(&ld),
+            &float_pre_trust,
+            &float_config,
+        )
+        .unwrap();
+
+        let bound = size as f64 / scale as f64;
+        for (fixed, float) in scores.trust.iter().zip(&float.trust) {
+            assert!((from_fixed(*fixed, scale) - float).abs() <= bound);
+        }
+        for (fixed, float) in scores.distrust.iter().zip(&float.distrust) {
+            assert!((from_fixed(*fixed, scale) - float).abs() <= bound);
+        }
+    }
+
+    #[test]
+    fn out_of_range_entries_are_rejected() {
+        let (mut lt, ld, pre_trust) = graph();
+        lt.push((1, 4, 100));
+        let error = run(&lt, &ld, &pre_trust, &config()).unwrap_err();
+        let FixedPointError::Invalid(error) = error else {
+            panic!("expected a validation error, found {}", error);
+        };
+        assert_eq!(error.violations[0].rule, Rule::OutOfRange { size: 4 });
+        assert!(negative_run(&[(5, 0, 1)], &pre_trust, &config()).is_err());
+    }
+
+    #[test]
+    fn invalid_pre_trust_is_rejected() {
+        assert_eq!(
+            to_fixed_pre_trust(&[0.25, 0.75], 1000).unwrap(),
+            vec![250, 750]
+        );
+        for bad in [-0.5, f64::NAN, f64::INFINITY] {
+            assert!(to_fixed_pre_trust(&[0.5, bad], 1000).is_err());
+        }
+    }
+}
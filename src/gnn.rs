@@ -1,28 +1,28 @@
-use std::array::from_fn;
-
 use rand::{thread_rng, Rng};
 use rustydiff::reverse::{Diff, ScalarOps, Tape, Var};
 
-const NUM_NEIGHBOURS: usize = 5;
+use crate::linalg::Vector;
+use crate::utils::format_vec;
 
 fn forward_run<'a, const NUM_ITER: usize>(
     tp: &'a Tape<f32, ScalarOps>,
-    weights: &[[Option<Var<'a, f32, ScalarOps>>; NUM_NEIGHBOURS]; NUM_NEIGHBOURS],
-    biases: &[Var<'a, f32, ScalarOps>; NUM_NEIGHBOURS],
-    seed: &[Var<'a, f32, ScalarOps>; NUM_NEIGHBOURS],
-) -> [Var<'a, f32, ScalarOps>; NUM_NEIGHBOURS] {
-    let mut s: [Var<f32, ScalarOps>; NUM_NEIGHBOURS] = from_fn(|_| tp.var(0.));
+    weights: &[Vec<Option<Var<'a, f32, ScalarOps>>>],
+    biases: &[Var<'a, f32, ScalarOps>],
+    seed: &[Var<'a, f32, ScalarOps>],
+) -> Vec<Var<'a, f32, ScalarOps>> {
+    let num_neighbours = weights.len();
+    let mut s: Vec<Var<f32, ScalarOps>> = (0..num_neighbours).map(|_| tp.var(0.)).collect();
     for i in 0..seed.len() {
         s[i] = tp.var(seed[i].data);
     }
 
     for _ in 0..NUM_ITER {
-        let mut new_s = from_fn(|_| tp.var(0.));
+        let mut new_s: Vec<Var<f32, ScalarOps>> = (0..num_neighbours).map(|_| tp.var(0.)).collect();
 
         // Compute sum of incoming weights
-        for i in 0..NUM_NEIGHBOURS {
+        for i in 0..num_neighbours {
             // Aggregate - agg = (w_ji/W_j) * x_j
-            for j in 0..NUM_NEIGHBOURS {
+            for j in 0..num_neighbours {
                 if let Some(w) = &weights[i][j] {
                     new_s[i] += w * &s[j];
                 }
@@ -37,72 +37,63 @@ fn forward_run<'a, const NUM_ITER: usize>(
     s
 }
 
-pub fn run_job() {
+// Learned parameters of the best training run
+#[derive(Debug, Clone)]
+pub struct TrainedModel {
+    pub weights: Vec<Vec<Option<f32>>>,
+    pub biases: Vector,
+    pub scores: Vector,
+    pub error: f32,
+}
+
+// Learn edge weights and biases so the labeled peers end up with their desired scores.
+// `edges[i][j]` marks that peer `i` aggregates the score of peer `j`.
+pub fn train(
+    edges: &[Vec<bool>],
+    initial_biases: &[f32],
+    labels: &[(usize, f32)],
+    num_runs: usize,
+    num_steps: usize,
+) -> TrainedModel {
     let tp = Tape::<f32, ScalarOps>::new();
 
     let mut rng = thread_rng();
 
-    // Labels - desired scores for peer 0, 3, and 4 - the rest will be learned by the network
-    let labels = vec![(0, tp.var(0.0)), (3, tp.var(0.2)), (4, tp.var(0.3))];
+    let labels: Vec<(usize, Var<f32, ScalarOps>)> =
+        labels.iter().map(|(i, x)| (*i, tp.var(*x))).collect();
 
-    let mut err = 100.0;
+    let mut best = TrainedModel {
+        weights: Vec::new(),
+        biases: Vec::new(),
+        scores: Vec::new(),
+        error: f32::MAX,
+    };
 
     // Starting with random seed values at each training run
-    let seed = [
-        tp.var(rng.gen_range(0.0..1.0)),
-        tp.var(rng.gen_range(0.0..1.0)),
-        tp.var(rng.gen_range(0.0..1.0)),
-        tp.var(rng.gen_range(0.0..1.0)),
-        tp.var(rng.gen_range(0.0..1.0)),
-    ];
+    let seed: Vec<Var<f32, ScalarOps>> = edges
+        .iter()
+        .map(|_| tp.var(rng.gen_range(0.0..1.0)))
+        .collect();
 
-    for _ in 0..100 {
+    for _ in 0..num_runs {
         // Initial weights
-        let mut weights: [[Option<Var<f32, ScalarOps>>; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-            [
-                None,
-                None,
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-            ],
-            [
-                None,
-                None,
-                None,
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-                None,
-            ],
-            [
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-                None,
-                None,
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-            ],
-            [
-                None,
-                None,
-                None,
-                None,
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-            ],
-            [
-                None,
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-                Some(tp.var(rng.gen_range(0.0..1.0))),
-                None,
-                None,
-            ],
-        ];
+        let mut weights: Vec<Vec<Option<Var<f32, ScalarOps>>>> = edges
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|edge| edge.then(|| tp.var(rng.gen_range(0.0..1.0))))
+                    .collect()
+            })
+            .collect();
         // Initial biases
-        let mut biases = [tp.var(0.), tp.var(0.), tp.var(0.), tp.var(0.1), tp.var(0.1)];
+        let mut biases: Vec<Var<f32, ScalarOps>> =
+            initial_biases.iter().map(|x| tp.var(*x)).collect();
         // Learning rate starting value
         let mut lr = 0.01;
 
         // ------------------------------------------------------------------
 
-        for i in 0..100 {
+        for _ in 0..num_steps {
             // Do the message passing
             let res = forward_run::<20>(&tp, &weights, &biases, &seed);
 
@@ -121,28 +112,24 @@ pub fn run_job() {
                 error += &(label - &res[*index]).powf(&tp.var(2.0)) * &abs.powf(&tp.var(-1.));
             }
 
-            if error.data < err {
-                err = error.data;
-                println!("smallest error: {}", error.data);
-                println!(
-                    "end: [{}]",
-                    res.iter()
-                        .map(|v| format!("{:>9.4}", v.data.clone()))
-                        .collect::<String>()
-                );
-            }
-
-            // Print out the error every N iterations
-            if i % 99 == 0 {
-                println!("curr_err: {}", error.data);
+            if error.data < best.error {
+                best = TrainedModel {
+                    weights: weights
+                        .iter()
+                        .map(|row| row.iter().map(|w| w.as_ref().map(|w| w.data)).collect())
+                        .collect(),
+                    biases: biases.iter().map(|b| b.data).collect(),
+                    scores: res.iter().map(|v| v.data).collect(),
+                    error: error.data,
+                };
             }
 
             // Calculate the gradient for all variables
             error.reverse();
 
             // Update weights and bisases based on the error
-            for i in 0..NUM_NEIGHBOURS {
-                for j in 0..NUM_NEIGHBOURS {
+            for i in 0..weights.len() {
+                for j in 0..weights[i].len() {
                     if let Some(w) = &weights[i][j] {
                         weights[i][j] = Some(tp.var(w.data - w.grad() * lr));
                     }
@@ -154,4 +141,23 @@ pub fn run_job() {
             lr = 0.999 * lr;
         }
     }
+
+    best
+}
+
+pub fn run_job() {
+    let edges = vec![
+        vec![false, false, true, true, true],
+        vec![false, false, false, true, false],
+        vec![true, false, false, true, true],
+        vec![false, false, false, false, true],
+        vec![false, true, true, false, false],
+    ];
+    let biases = vec![0., 0., 0., 0.1, 0.1];
+    // Labels - desired scores for peer 0, 3, and 4 - the rest will be learned by the network
+    let labels = vec![(0, 0.0), (3, 0.2), (4, 0.3)];
+
+    let model = train(&edges, &biases, &labels, 100, 100);
+    println!("smallest error: {}", model.error);
+    println!("end: [{}]", format_vec(&model.scores));
 }
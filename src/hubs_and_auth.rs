@@ -1,58 +1,103 @@
-use crate::utils::{normalise_sqrt, transpose};
+use serde::{Deserialize, Serialize};
+
+use crate::linalg::{mul_transposed, normalise, transpose, Matrix, Norm, Scalar, Vector};
+use crate::utils::{format_vec, Convergence, ConvergenceReport, Residual};
 
-const NUM_NEIGHBOURS: usize = 5;
 const NUM_ITER: usize = 50;
+const TOLERANCE: f64 = 1e-6;
 
-fn run(
-    am: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS],
-    initial_state_hubs: [f32; NUM_NEIGHBOURS],
-    initial_state_auth: [f32; NUM_NEIGHBOURS],
-) -> ([f32; NUM_NEIGHBOURS], [f32; NUM_NEIGHBOURS]) {
-    let mut s_hubs = initial_state_hubs.clone();
-    let mut s_auth = initial_state_auth.clone();
-    let transposed_am = transpose(am);
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HitsConfig {
+    pub max_iter: usize,
+    pub tolerance: f64,
+    pub residual: Residual,
+}
 
-    println!("start hubs: [{}]", s_hubs.map(|v| format!("{:>9.4}", v)).join(", "));
-    println!("start auth: [{}]", s_auth.map(|v| format!("{:>9.4}", v)).join(", "));
-    for _ in 0..NUM_ITER {
-        let mut new_s_hubs = [0.; 5];
-        let mut new_s_auth = [0.; 5];
+impl Default for HitsConfig {
+    fn default() -> Self {
+        Self {
+            max_iter: NUM_ITER,
+            tolerance: TOLERANCE,
+            residual: Residual::L1,
+        }
+    }
+}
 
-        // Hubs
-        for i in 0..NUM_NEIGHBOURS {
-            for j in 0..NUM_NEIGHBOURS {
-                new_s_hubs[i] += am[j][i] * s_auth[j];
-            }
+impl HitsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        self.convergence().validate()
+    }
+
+    pub fn convergence(&self) -> Convergence {
+        Convergence {
+            tolerance: self.tolerance,
+            max_iter: self.max_iter,
+            residual: self.residual,
         }
+    }
+}
+
+pub fn run<T: Scalar>(
+    am: &[Vec<T>],
+    initial_state_hubs: &[T],
+    initial_state_auth: &[T],
+    config: &HitsConfig,
+) -> (Vector<T>, Vector<T>, ConvergenceReport) {
+    let convergence = config.convergence();
+    let mut s_hubs = initial_state_hubs.to_vec();
+    let mut s_auth = initial_state_auth.to_vec();
+    let transposed_am = transpose(am);
+
+    let mut report = ConvergenceReport::default();
+    for iteration in 1..=convergence.max_iter {
+        // Hubs
+        let new_s_hubs = mul_transposed(am, &s_auth);
         // Authorities
-        for i in 0..NUM_NEIGHBOURS {
-            for j in 0..NUM_NEIGHBOURS {
-                new_s_auth[i] += transposed_am[j][i] * s_hubs[j];
-            }
-        }
+        let new_s_auth = mul_transposed(&transposed_am, &s_hubs);
 
-        let final_s_hubs = normalise_sqrt(new_s_hubs);
-        let final_s_auth = normalise_sqrt(new_s_auth);
+        let final_s_hubs = normalise(&new_s_hubs, Norm::L2, None);
+        let final_s_auth = normalise(&new_s_auth, Norm::L2, None);
+
+        // Both vectors have to settle - report the larger of the two residuals
+        let hubs_report = convergence.check(iteration, &s_hubs, &final_s_hubs);
+        let auth_report = convergence.check(iteration, &s_auth, &final_s_auth);
+        report = if hubs_report.residual >= auth_report.residual {
+            hubs_report
+        } else {
+            auth_report
+        };
 
         s_hubs = final_s_hubs;
         s_auth = final_s_auth;
+        if report.converged {
+            break;
+        }
     }
-    println!("end hubs: [{}]", s_hubs.map(|v| format!("{:>9.4}", v)).join(", "));
-    println!("end auth: [{}]", s_auth.map(|v| format!("{:>9.4}", v)).join(", "));
 
-    (s_hubs, s_auth)
+    (s_hubs, s_auth, report)
 }
 
 pub fn run_job() {
     // From hubs to authorities
-    let adjacency_matrix: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-      [0., 1., 0., 1., 0.],
-      [0., 0., 0., 1., 0.],
-      [1., 0., 0., 1., 1.],
-      [0., 1., 0., 0., 0.],
-      [0., 1., 0., 1., 0.],
+    let adjacency_matrix: Matrix = vec![
+        vec![0., 1., 0., 1., 0.],
+        vec![0., 0., 0., 1., 0.],
+        vec![1., 0., 0., 1., 1.],
+        vec![0., 1., 0., 0., 0.],
+        vec![0., 1., 0., 1., 0.],
     ];
-    let initial_state_hubs = [0.32, 0.0, 0.22, 0.0, 0.66];
-    let initial_state_auth = [0.32, 0.11, 0.14, 0.1, 0.33];
-    run(adjacency_matrix, initial_state_hubs, initial_state_auth);
+    let initial_state_hubs = vec![0.32, 0.0, 0.22, 0.0, 0.66];
+    let initial_state_auth = vec![0.32, 0.11, 0.14, 0.1, 0.33];
+    println!("start hubs: [{}]", format_vec(&initial_state_hubs));
+    println!("start auth: [{}]", format_vec(&initial_state_auth));
+    let (s_hubs, s_auth, report) = run(
+        &adjacency_matrix,
+        &initial_state_hubs,
+        &initial_state_auth,
+        &HitsConfig::default(),
+    );
+    println!("end hubs: [{}]", format_vec(&s_hubs));
+    println!("end auth: [{}]", format_vec(&s_auth));
+    println!("{}", report);
 }
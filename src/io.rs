@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::eigen_trust::validate_pre_trust;
+use crate::linalg::{normalise, Norm, Scalar, Vector};
+use crate::sparse::CsrMatrix;
+
+// Maps arbitrary peer identifiers to matrix indices, in order of first appearance
+#[derive(Debug, Clone, Default)]
+pub struct PeerIndex {
+    ids: Vec<String>,
+    indices: HashMap<String, usize>,
+}
+
+impl PeerIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, id: &str) -> usize {
+        if let Some(index) = self.indices.get(id) {
+            return *index;
+        }
+        let index = self.ids.len();
+        self.ids.push(id.to_string());
+        self.indices.insert(id.to_string(), index);
+        index
+    }
+
+    pub fn get(&self, id: &str) -> Option<usize> {
+        self.indices.get(id).cloned()
+    }
+
+    pub fn id(&self, index: usize) -> &str {
+        &self.ids[index]
+    }
+
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn index_edges<T: Copy>(
+        &mut self,
+        edges: &[(String, String, T)],
+    ) -> Vec<(usize, usize, T)> {
+        edges
+            .iter()
+            .map(|(from, to, value)| (self.insert(from), self.insert(to), *value))
+            .collect()
+    }
+
+    pub fn index_values<T: Copy>(&mut self, values: &[(String, T)]) -> Vec<(usize, T)> {
+        values
+            .iter()
+            .map(|(peer, value)| (self.insert(peer), *value))
+            .collect()
+    }
+}
+
+// Local trust, local distrust and pre-trust over a shared set of peers
+#[derive(Debug, Clone)]
+pub struct TrustInput<T = f32> {
+    pub peers: PeerIndex,
+    pub lt: CsrMatrix<T>,
+    pub ld: CsrMatrix<T>,
+    pub pre_trust: Vector<T>,
+}
+
+// Load EigenTrust inputs from edge lists. Negative values in the trust file are read as
+// distrust of the same magnitude, so a single signed file can carry both. Values are parsed
+// straight into `T`, so `f64` inputs are never rounded through `f32`. The pre-trust must be
+// finite, non-negative and not all zero, and is scaled to sum to 1.
+pub fn load_trust<T: Scalar + FromStr>(
+    trust: &Path,
+    distrust: Option<&Path>,
+    pre_trust: &Path,
+) -> io::Result<TrustInput<T>> {
+    let mut peers = PeerIndex::new();
+    let mut lt = Vec::new();
+    let mut ld = Vec::new();
+    for (i, j, value) in peers.index_edges(&read_edges::<T>(trust)?) {
+        if value < T::zero() {
+            ld.push((i, j, -value));
+        } else {
+            lt.push((i, j, value));
+        }
+    }
+    if let Some(distrust) = distrust {
+        ld.extend(peers.index_edges(&read_edges(distrust)?));
+    }
+    let pre_trust_path = pre_trust;
+    let pre_trust = peers.index_values(&read_values(pre_trust_path)?);
+
+    let size = peers.len();
+    let pre_trust = to_vector(&pre_trust, size, T::zero());
+    let invalid = |message: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", pre_trust_path.display(), message),
+        )
+    };
+    validate_pre_trust(&pre_trust).map_err(|e| invalid(e.describe(|i| peers.id(i))))?;
+    if pre_trust.iter().all(|x| *x == T::zero()) {
+        return Err(invalid("pre-trust is zero for every peer".to_string()));
+    }
+    Ok(TrustInput {
+        lt: CsrMatrix::from_triplets(size, lt),
+        ld: CsrMatrix::from_triplets(size, ld),
+        pre_trust: normalise(&pre_trust, Norm::L1, None),
+        peers,
+    })
+}
+
+// Dense vector of `size` entries, `default` wherever no value is given
+pub fn to_vector<T: Copy>(values: &[(usize, T)], size: usize, default: T) -> Vector<T> {
+    let mut out = vec![default; size];
+    for (i, value) in values {
+        out[*i] = *value;
+    }
+    out
+}
+
+fn invalid_data(path: &Path, line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{}: {}", path.display(), line, message),
+    )
+}
+
+// The last column is always a number, so a first row naming it (`value`, `score`) is a header.
+// A value with digits in it is a malformed number, never a column name.
+fn is_header(field: &str) -> bool {
+    !field.is_empty()
+        && field.parse::<f64>().is_err()
+        && field
+            .chars()
+            .all(|c| c.is_alphabetic() || c == '_' || c == ' ')
+}
+
+// Read comma separated rows - blank lines, `#` comments and a header row are skipped
+fn read_rows(path: &Path, num_fields: usize) -> io::Result<Vec<(usize, Vec<String>)>> {
+    let content = fs::read_to_string(path)?;
+    let mut rows = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<String> = line.split(',').map(|x| x.trim().to_string()).collect();
+        if fields.len() != num_fields {
+            let message = format!("expected {} fields, found {}", num_fields, fields.len());
+            return Err(invalid_data(path, i + 1, &message));
+        }
+        if rows.is_empty() && is_header(&fields[num_fields - 1]) {
+            continue;
+        }
+        rows.push((i + 1, fields));
+    }
+    Ok(rows)
+}
+
+fn parse_value<T: FromStr>(path: &Path, line: usize, field: &str) -> io::Result<T> {
+    field
+        .parse()
+        .map_err(|_| invalid_data(path, line, &format!("invalid value `{}`", field)))
+}
+
+// Read a `from,to,value` edge list
+pub fn read_edges<T: FromStr>(path: &Path) -> io::Result<Vec<(String, String, T)>> {
+    let mut edges = Vec::new();
+    for (line, fields) in read_rows(path, 3)? {
+        let value = parse_value(path, line, &fields[2])?;
+        edges.push((fields[0].clone(), fields[1].clone(), value));
+    }
+    Ok(edges)
+}
+
+// Read a `peer,value` list
+pub fn read_values<T: FromStr>(path: &Path) -> io::Result<Vec<(String, T)>> {
+    let mut values = Vec::new();
+    for (line, fields) in read_rows(path, 2)? {
+        let value = parse_value(path, line, &fields[1])?;
+        values.push((fields[0].clone(), value));
+    }
+    Ok(values)
+}
+
+// Write `peer,score` rows, mapping positions in `scores` back to peer ids
+pub fn write_scores<W: Write, T: fmt::Display>(
+    writer: &mut W,
+    peers: &PeerIndex,
+    scores: &[T],
+) -> io::Result<()> {
+    writeln!(writer, "peer,score")?;
+    for (i, score) in scores.iter().enumerate() {
+        writeln!(writer, "{},{}", peers.id(i), score)?;
+    }
+    Ok(())
+}
+
+pub fn save_scores<T: fmt::Display>(
+    path: &Path,
+    peers: &PeerIndex,
+    scores: &[T],
+) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    write_scores(&mut file, peers, scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_is_a_column_name() {
+        assert!(is_header("value"));
+        assert!(is_header("trust_score"));
+        assert!(!is_header("1.5"));
+        assert!(!is_header("-2e3"));
+        assert!(!is_header("1.O"));
+        assert!(!is_header(""));
+        assert!(!is_header("NaN"));
+    }
+
+    fn write_temp(name: &str, content: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("eigen_trust_io_{}_{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn pre_trust_is_checked_and_normalised() {
+        let trust = write_temp("trust.csv", "alice,bob,1\nbob,alice,1\n");
+        let pre_trust = write_temp("pre_trust.csv", "alice,1\nzed,1\n");
+        let input = load_trust::<f64>(&trust, None, &pre_trust).unwrap();
+        assert_eq!(input.peers.ids(), ["alice", "bob", "zed"]);
+        assert_eq!(input.pre_trust, vec![0.5, 0., 0.5]);
+
+        for content in ["alice,-1\nbob,1\n", "alice,NaN\n", "alice,0\nbob,0\n"] {
+            let pre_trust = write_temp("bad_pre_trust.csv", content);
+            let error = load_trust::<f64>(&trust, None, &pre_trust).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", content);
+        }
+    }
+}
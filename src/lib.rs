@@ -0,0 +1,14 @@
+pub mod commitment;
+pub mod config;
+pub mod domain;
+pub mod eigen_trust;
+pub mod fixed_point;
+pub mod gnn;
+pub mod hubs_and_auth;
+pub mod io;
+pub mod linalg;
+pub mod page_rank;
+pub mod snap;
+pub mod sparse;
+pub mod transitive_trust;
+pub mod utils;
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::iter::Sum;
+
+use num_traits::{Float, NumAssignOps};
+use serde::{Deserialize, Serialize};
+
+// Float type the scoring core runs in - f32 for the fast paths, f64 for audits
+pub trait Scalar:
+    Float + NumAssignOps + Sum + fmt::Debug + fmt::Display + Send + Sync + 'static
+{
+}
+
+impl<T> Scalar for T where
+    T: Float + NumAssignOps + Sum + fmt::Debug + fmt::Display + Send + Sync + 'static
+{
+}
+
+pub type Vector<T = f32> = Vec<T>;
+pub type Matrix<T = f32> = Vec<Vec<T>>;
+
+// Convert between precisions, e.g. f32 parameters into an f64 run
+pub fn cast<T: Scalar, U: Scalar>(x: T) -> U {
+    U::from(x).unwrap()
+}
+
+pub fn cast_vec<T: Scalar, U: Scalar>(v: &[T]) -> Vector<U> {
+    v.iter().map(|x| cast(*x)).collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Norm {
+    // Sum of absolute values - turns non-negative scores into a distribution
+    L1,
+    // Euclidean length
+    L2,
+    // Largest absolute value
+    Max,
+}
+
+impl Norm {
+    pub fn of<T: Scalar>(&self, v: &[T]) -> T {
+        match self {
+            Norm::L1 => v.iter().map(|x| x.abs()).sum(),
+            Norm::L2 => v.iter().map(|x| *x * *x).sum::<T>().sqrt(),
+            Norm::Max => v.iter().fold(T::zero(), |max, x| x.abs().max(max)),
+        }
+    }
+}
+
+// Scale `v` to unit `norm`. A zero vector is replaced by `fallback` (e.g. the pre-trust),
+// or returned as is when there is none.
+pub fn normalise<T: Scalar>(v: &[T], norm: Norm, fallback: Option<&[T]>) -> Vector<T> {
+    let length = norm.of(v);
+    if length == T::zero() {
+        return fallback.unwrap_or(v).to_vec();
+    }
+    v.iter().map(|x| *x / length).collect()
+}
+
+pub fn transpose<T: Scalar>(s: &[Vec<T>]) -> Matrix<T> {
+    let n = s.len();
+    let mut new_s: Matrix<T> = vec![vec![T::zero(); n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            new_s[i][j] = s[j][i];
+        }
+    }
+    new_s
+}
+
+// Dense matrix-vector product of the transpose: out[i] = sum_j m[j][i] * s[j]
+pub fn mul_transposed<T: Scalar>(m: &[Vec<T>], s: &[T]) -> Vector<T> {
+    let n = m.len();
+    let mut out = vec![T::zero(); n];
+    for i in 0..n {
+        for j in 0..n {
+            out[i] += m[j][i] * s[j];
+        }
+    }
+    out
+}
+
+pub fn vec_scalar_mul<T: Scalar>(s: &[T], y: T) -> Vector<T> {
+    s.iter().map(|x| *x * y).collect()
+}
+
+pub fn vec_add<T: Scalar>(s: &[T], y: &[T]) -> Vector<T> {
+    s.iter().zip(y).map(|(a, b)| *a + *b).collect()
+}
+
+pub fn vec_sub<T: Scalar>(s: &[T], y: &[T]) -> Vector<T> {
+    s.iter().zip(y).map(|(a, b)| *a - *b).collect()
+}
@@ -1,10 +1,9 @@
-mod eigen_trust;
-mod gnn;
-mod hubs_and_auth;
-mod page_rank;
-mod transitive_trust;
-mod utils;
+mod cli;
 
 fn main() {
-    transitive_trust::run_job();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = cli::run(&args) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -1,52 +1,179 @@
-use crate::utils::{normalise, vec_add};
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::linalg::{cast, vec_add, vec_scalar_mul, Scalar, Vector};
+use crate::sparse::CsrMatrix;
+use crate::utils::{format_vec, Convergence, ConvergenceReport, Execution, Residual};
 
-const NUM_NEIGHBOURS: usize = 5;
 const NUM_ITER: usize = 50;
-const DAMPENING_AMOUNT: f32 = 0.2;
-
-fn run(
-    mut am: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS],
-    pre_trust: [f32; NUM_NEIGHBOURS],
-    seed: [f32; NUM_NEIGHBOURS],
-) -> [f32; NUM_NEIGHBOURS] {
-    for i in 0..NUM_NEIGHBOURS {
-        am[i] = normalise(am[i], pre_trust);
+const TOLERANCE: f64 = 1e-6;
+const DAMPENING_AMOUNT: f64 = 0.2;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PageRankConfig {
+    pub dampening_amount: f64,
+    pub max_iter: usize,
+    pub tolerance: f64,
+    pub residual: Residual,
+    pub execution: Execution,
+}
+
+impl Default for PageRankConfig {
+    fn default() -> Self {
+        Self {
+            dampening_amount: DAMPENING_AMOUNT,
+            max_iter: NUM_ITER,
+            tolerance: TOLERANCE,
+            residual: Residual::L1,
+            execution: Execution::Sequential,
+        }
+    }
+}
+
+impl PageRankConfig {
+    pub fn validate(&self) -> Result<(), PageRankError> {
+        if !(0. ..=1.).contains(&self.dampening_amount) {
+            return Err(PageRankError::Config(format!(
+                "dampening_amount must be in [0, 1], found {}",
+                self.dampening_amount
+            )));
+        }
+        self.convergence().validate().map_err(PageRankError::Config)
     }
 
-    let mut s = seed.clone();
-    let pre_trusted_scores = pre_trust.map(|x| x * DAMPENING_AMOUNT);
+    pub fn convergence(&self) -> Convergence {
+        Convergence {
+            tolerance: self.tolerance,
+            max_iter: self.max_iter,
+            residual: self.residual,
+        }
+    }
+}
 
-    println!("start: [{}]", s.map(|v| format!("{:>9.4}", v)).join(", "));
-    for _ in 0..NUM_ITER {
-        let mut new_s = [0.; 5];
+#[derive(Debug, Clone, PartialEq)]
+pub enum PageRankError {
+    Config(String),
+    // The pre-trust or seed covers a different number of nodes than the graph
+    Size {
+        name: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
 
-        // Compute sum of incoming weights
-        for i in 0..NUM_NEIGHBOURS {
-            for j in 0..NUM_NEIGHBOURS {
-                new_s[i] += am[j][i] * s[j];
-            }
+impl fmt::Display for PageRankError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PageRankError::Config(message) => write!(f, "invalid page_rank config: {}", message),
+            PageRankError::Size {
+                name,
+                expected,
+                found,
+            } => write!(f, "{} covers {} nodes, expected {}", name, found, expected),
         }
+    }
+}
+
+impl Error for PageRankError {}
+
+pub fn run<T: Scalar>(
+    am: &CsrMatrix<T>,
+    pre_trust: &[T],
+    seed: &[T],
+    config: &PageRankConfig,
+) -> Result<(Vector<T>, ConvergenceReport), PageRankError> {
+    config.validate()?;
+    for (name, v) in [("pre-trust", pre_trust), ("seed", seed)] {
+        if v.len() != am.size() {
+            return Err(PageRankError::Size {
+                name,
+                expected: am.size(),
+                found: v.len(),
+            });
+        }
+    }
+    let convergence = config.convergence();
+    // Incoming weights - row `i` holds the normalised links pointing to node `i`.
+    // Nodes without links spread their score over the pre-trust instead.
+    let am_t = am.normalise_rows().transpose();
+    let dangling = am.empty_rows();
+
+    let mut s = seed.to_vec();
+    let dampening_amount: T = cast(config.dampening_amount);
+    let pre_trusted_scores = vec_scalar_mul(pre_trust, dampening_amount);
+
+    let mut report = ConvergenceReport::default();
+    for iteration in 1..=convergence.max_iter {
+        // Compute sum of incoming weights
+        let new_s = am_t.mul_vec_with(&s, config.execution);
+        let dangling_mass: T = dangling.iter().map(|i| s[*i]).sum();
+        let new_s = vec_add(&new_s, &vec_scalar_mul(pre_trust, dangling_mass));
 
-        let global_scores = new_s.map(|x| (1. - DAMPENING_AMOUNT) * x);
-        let current_s = vec_add(pre_trusted_scores, global_scores);
+        let global_scores = vec_scalar_mul(&new_s, T::one() - dampening_amount);
+        let current_s = vec_add(&pre_trusted_scores, &global_scores);
 
+        report = convergence.check(iteration, &s, &current_s);
         s = current_s;
+        if report.converged {
+            break;
+        }
     }
-    println!("end: [{}]", s.map(|v| format!("{:>9.4}", v)).join(", "));
 
-    s
+    Ok((s, report))
 }
 
 pub fn run_job() {
     // From hubs to authorities
-    let adjacency_matrix: [[f32; NUM_NEIGHBOURS]; NUM_NEIGHBOURS] = [
-        [0., 0., 1., 1., 1.],
-        [0., 0., 0., 1., 0.],
-        [1., 0., 0., 1., 1.],
-        [0., 0., 0., 0., 1.],
-        [0., 1., 1., 0., 0.],
-    ];
-    let pre_trust = [0., 0., 0., 5., 5.];
-    let seed = [1., 4., 1., 1., 1.];
-    run(adjacency_matrix, pre_trust, seed);
+    let adjacency_matrix = CsrMatrix::from_dense(&[
+        vec![0., 0., 1., 1., 1.],
+        vec![0., 0., 0., 1., 0.],
+        vec![1., 0., 0., 1., 1.],
+        vec![0., 0., 0., 0., 1.],
+        vec![0., 1., 1., 0., 0.],
+    ]);
+    let pre_trust: Vector = vec![0., 0., 0., 5., 5.];
+    let seed: Vector = vec![1., 4., 1., 1., 1.];
+    println!("start: [{}]", format_vec(&seed));
+    let (s, report) = run(
+        &adjacency_matrix,
+        &pre_trust,
+        &seed,
+        &PageRankConfig::default(),
+    )
+    .unwrap();
+    println!("end: [{}]", format_vec(&s));
+    println!("{}", report);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_must_agree() {
+        let am = CsrMatrix::from_dense(&[vec![0., 1., 1.], vec![1., 0., 0.], vec![0., 1., 0.]]);
+        let config = PageRankConfig::default();
+        let short = [0.5, 0.5];
+        let full = [0.5, 0.5, 0.];
+        assert_eq!(
+            run(&am, &short, &full, &config).unwrap_err(),
+            PageRankError::Size {
+                name: "pre-trust",
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            run(&am, &full, &short, &config).unwrap_err(),
+            PageRankError::Size {
+                name: "seed",
+                expected: 3,
+                found: 2
+            }
+        );
+        assert!(run(&am, &full, &full, &config).is_ok());
+    }
 }
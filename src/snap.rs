@@ -0,0 +1,1100 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::linalg::{cast, Scalar};
+use crate::utils::Execution;
+
+const CONFIDENCE_THRESHOLD: f64 = 0.3;
+const REPORT_THRESHOLD: f64 = 0.3;
+const SECURE_THRESHOLD: f64 = 0.7;
+
+// Uniform prior, and one pseudo-count per 0.1 of trust behind a vote
+const PRIOR: f64 = 1.;
+const EVIDENCE_WEIGHT: f64 = 10.;
+const CREDIBLE_MASS: f64 = 0.9;
+const DECISION: f64 = 0.5;
+const MAX_WIDTH: f64 = 0.5;
+// Bisection steps for the Beta quantiles - far below f32 precision
+const QUANTILE_ITER: usize = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapConfig {
+    pub policy: ThresholdPolicy,
+    // Cut-offs of the `fixed` policy
+    pub confidence_threshold: f64,
+    pub report_threshold: f64,
+    pub secure_threshold: f64,
+    pub beta: BetaConfig,
+}
+
+impl Default for SnapConfig {
+    fn default() -> Self {
+        Self {
+            policy: ThresholdPolicy::default(),
+            confidence_threshold: CONFIDENCE_THRESHOLD,
+            report_threshold: REPORT_THRESHOLD,
+            secure_threshold: SECURE_THRESHOLD,
+            beta: BetaConfig::default(),
+        }
+    }
+}
+
+// Where the classification cut-offs come from
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdPolicy {
+    // `confidence_threshold`, `report_threshold` and `secure_threshold` as configured
+    #[default]
+    Fixed,
+    // One threshold `t` for confidence and reports, and `1 - t` for endorsements
+    Symmetric {
+        threshold: f64,
+    },
+    // Symmetric around the smallest non-zero pre-trust, see `calculate_snap_score_threshold`
+    MinPreTrust,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapConfigError(pub String);
+
+impl fmt::Display for SnapConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid snap config: {}", self.0)
+    }
+}
+
+impl Error for SnapConfigError {}
+
+impl SnapConfig {
+    // Cut-offs of the policy in use - `beta` has its own `BetaConfig::validate`
+    pub fn validate(&self) -> Result<(), SnapConfigError> {
+        match self.policy {
+            ThresholdPolicy::Fixed => {
+                for (name, threshold) in [
+                    ("confidence_threshold", self.confidence_threshold),
+                    ("report_threshold", self.report_threshold),
+                    ("secure_threshold", self.secure_threshold),
+                ] {
+                    if !(0. ..=1.).contains(&threshold) {
+                        return Err(SnapConfigError(format!(
+                            "{} must be in [0, 1], found {}",
+                            name, threshold
+                        )));
+                    }
+                }
+                if self.report_threshold > self.secure_threshold {
+                    return Err(SnapConfigError(format!(
+                        "report_threshold {} is above secure_threshold {}",
+                        self.report_threshold, self.secure_threshold
+                    )));
+                }
+            }
+            // `1 - threshold` must not fall below `threshold`
+            ThresholdPolicy::Symmetric { threshold } => {
+                if !(0. ..=0.5).contains(&threshold) {
+                    return Err(SnapConfigError(format!(
+                        "symmetric threshold must be in [0, 0.5], found {}",
+                        threshold
+                    )));
+                }
+            }
+            ThresholdPolicy::MinPreTrust => {}
+        }
+        Ok(())
+    }
+
+    // Cut-offs in the precision of the scores - `f64` parameters are only rounded for `f32`
+    pub fn thresholds<T: Scalar>(&self, pre_trust: &[T]) -> Thresholds<T> {
+        match self.policy {
+            ThresholdPolicy::Fixed => Thresholds {
+                confidence: cast(self.confidence_threshold),
+                report: cast(self.report_threshold),
+                endorse: cast(self.secure_threshold),
+            },
+            ThresholdPolicy::Symmetric { threshold } => Thresholds::symmetric(cast(threshold)),
+            ThresholdPolicy::MinPreTrust => {
+                Thresholds::symmetric(calculate_snap_score_threshold(pre_trust))
+            }
+        }
+    }
+}
+
+// A snap is unverified up to `confidence`. Past it, the snap is reported up to a score of
+// `report`, endorsed above `endorse` and contested in between. Reports are checked first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds<T = f32> {
+    pub confidence: T,
+    pub report: T,
+    pub endorse: T,
+}
+
+impl<T: Scalar> Thresholds<T> {
+    pub fn symmetric(threshold: T) -> Self {
+        Self {
+            confidence: threshold,
+            report: threshold,
+            endorse: T::one() - threshold,
+        }
+    }
+
+    pub fn cast<U: Scalar>(&self) -> Thresholds<U> {
+        Thresholds {
+            confidence: cast(self.confidence),
+            report: cast(self.report),
+            endorse: cast(self.endorse),
+        }
+    }
+
+    pub fn classify(&self, score: T, confidence: T) -> State {
+        if confidence <= self.confidence {
+            State::Unverified
+        } else if score <= self.report {
+            State::Reported
+        } else if score <= self.endorse {
+            State::Contested
+        } else {
+            State::Endorsed
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Thresholds<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "confidence: {}, report: {}, endorse: {}",
+            self.confidence, self.report, self.endorse
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Vote {
+    Endorse,
+    Report,
+}
+
+// A peer's opinion of a snap. The weight says how strongly the peer stands behind the vote.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Attestation {
+    pub peer: usize,
+    pub vote: Vote,
+    // In [0, 1]
+    pub weight: f32,
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+}
+
+impl Attestation {
+    pub fn endorse(peer: usize, weight: f32) -> Self {
+        Self {
+            peer,
+            vote: Vote::Endorse,
+            weight,
+            timestamp: None,
+        }
+    }
+
+    pub fn report(peer: usize, weight: f32) -> Self {
+        Self {
+            peer,
+            vote: Vote::Report,
+            weight,
+            timestamp: None,
+        }
+    }
+
+    pub fn at(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    // Whether `self` replaces `other` as the peer's vote. Timestamps decide when both have
+    // one, otherwise the attestation added last wins.
+    fn supersedes(&self, other: &Attestation) -> bool {
+        match (self.timestamp, other.timestamp) {
+            (Some(a), Some(b)) => a >= b,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttestationError {
+    InvalidWeight { peer: usize, weight: f32 },
+}
+
+impl fmt::Display for AttestationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttestationError::InvalidWeight { peer, weight } => write!(
+                f,
+                "attestation of peer {} has weight {}, expected a value in [0, 1]",
+                peer, weight
+            ),
+        }
+    }
+}
+
+impl Error for AttestationError {}
+
+// Every attestation submitted for a single snap, in the order they were added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ledger {
+    attestations: Vec<Attestation>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_attestations(attestations: Vec<Attestation>) -> Result<Self, AttestationError> {
+        let mut ledger = Self::new();
+        for attestation in attestations {
+            ledger.add(attestation)?;
+        }
+        Ok(ledger)
+    }
+
+    pub fn add(&mut self, attestation: Attestation) -> Result<(), AttestationError> {
+        if !(0. ..=1.).contains(&attestation.weight) {
+            return Err(AttestationError::InvalidWeight {
+                peer: attestation.peer,
+                weight: attestation.weight,
+            });
+        }
+        self.attestations.push(attestation);
+        Ok(())
+    }
+
+    pub fn attestations(&self) -> &[Attestation] {
+        &self.attestations
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attestations.is_empty()
+    }
+
+    // The vote that counts for each peer, keyed by peer
+    pub fn votes(&self) -> BTreeMap<usize, Attestation> {
+        let mut votes: BTreeMap<usize, Attestation> = BTreeMap::new();
+        for attestation in &self.attestations {
+            match votes.get(&attestation.peer) {
+                Some(current) if !attestation.supersedes(current) => {}
+                _ => {
+                    votes.insert(attestation.peer, *attestation);
+                }
+            }
+        }
+        votes
+    }
+}
+
+// Trust-weighted endorsements and reports. Peers with a negative score, or without a score at
+// all, carry no weight.
+fn tally<T: Scalar>(ledger: &Ledger, s: &[T]) -> (T, T) {
+    let mut endorse = T::zero();
+    let mut report = T::zero();
+    for (peer, attestation) in ledger.votes() {
+        let score = match s.get(peer) {
+            Some(score) if *score > T::zero() => *score,
+            _ => continue,
+        };
+        let weight: T = cast::<f32, T>(attestation.weight) * score;
+        match attestation.vote {
+            Vote::Endorse => endorse += weight,
+            Vote::Report => report += weight,
+        }
+    }
+    (endorse, report)
+}
+
+pub fn calculate_snap_score<T: Scalar>(ledger: &Ledger, s: &[T]) -> T {
+    let (num, den) = tally(ledger, s);
+    let snap_score: T = num / (num + den);
+
+    if snap_score.is_nan() {
+        T::zero()
+    } else {
+        snap_score
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    Reported,
+    Contested,
+    Endorsed,
+    Unverified,
+}
+
+pub fn calculate_snap_score_with_threshold<T: Scalar>(
+    ledger: &Ledger,
+    s: &[T],
+    thresholds: &Thresholds<T>,
+) -> (T, T, State) {
+    // num: trust-weighted endorsements, den: trust-weighted reports
+    let (num, den) = tally(ledger, s);
+    let snap_score: T = num / (num + den);
+
+    let score = if snap_score.is_nan() {
+        T::zero()
+    } else {
+        snap_score
+    };
+    let confidence = num + den;
+
+    (score, confidence, thresholds.classify(score, confidence))
+}
+
+// Snap scoring with a Beta posterior over the share of endorsements. Trust-weighted
+// endorsements and reports, times `evidence_weight`, are added to the prior pseudo-counts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BetaConfig {
+    // Beta(prior_endorse, prior_report) - both must be positive
+    pub prior_endorse: f64,
+    pub prior_report: f64,
+    // Pseudo-counts per unit of trust-weighted vote
+    pub evidence_weight: f64,
+    // Probability mass inside the equal-tailed credible interval
+    pub credible_mass: f64,
+    // Endorsed when the whole interval is above `decision`, reported when it is below
+    pub decision: f64,
+    // Unverified while the interval is wider than this
+    pub max_width: f64,
+}
+
+impl Default for BetaConfig {
+    fn default() -> Self {
+        Self {
+            prior_endorse: PRIOR,
+            prior_report: PRIOR,
+            evidence_weight: EVIDENCE_WEIGHT,
+            credible_mass: CREDIBLE_MASS,
+            decision: DECISION,
+            max_width: MAX_WIDTH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BetaConfigError(pub String);
+
+impl fmt::Display for BetaConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid snap.beta config: {}", self.0)
+    }
+}
+
+impl Error for BetaConfigError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BetaScore<T = f32> {
+    // Posterior mean
+    pub mean: T,
+    pub lower: T,
+    pub upper: T,
+    pub state: State,
+}
+
+impl BetaConfig {
+    pub fn validate(&self) -> Result<(), BetaConfigError> {
+        for (name, prior) in [
+            ("prior_endorse", self.prior_endorse),
+            ("prior_report", self.prior_report),
+        ] {
+            if !(prior > 0. && prior.is_finite()) {
+                return Err(BetaConfigError(format!(
+                    "{} must be a positive number, found {}",
+                    name, prior
+                )));
+            }
+        }
+        if !(self.evidence_weight >= 0. && self.evidence_weight.is_finite()) {
+            return Err(BetaConfigError(format!(
+                "evidence_weight must be a non-negative number, found {}",
+                self.evidence_weight
+            )));
+        }
+        if !(self.credible_mass > 0. && self.credible_mass < 1.) {
+            return Err(BetaConfigError(format!(
+                "credible_mass must be in (0, 1), found {}",
+                self.credible_mass
+            )));
+        }
+        if !(0. ..=1.).contains(&self.decision) {
+            return Err(BetaConfigError(format!(
+                "decision must be in [0, 1], found {}",
+                self.decision
+            )));
+        }
+        if !(self.max_width >= 0. && self.max_width.is_finite()) {
+            return Err(BetaConfigError(format!(
+                "max_width must be a non-negative number, found {}",
+                self.max_width
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn classify<T: Scalar>(&self, lower: T, upper: T) -> State {
+        let decision: T = cast(self.decision);
+        if upper - lower > cast(self.max_width) {
+            State::Unverified
+        } else if upper < decision {
+            State::Reported
+        } else if lower > decision {
+            State::Endorsed
+        } else {
+            State::Contested
+        }
+    }
+}
+
+pub fn calculate_beta_snap_score<T: Scalar>(
+    ledger: &Ledger,
+    s: &[T],
+    config: &BetaConfig,
+) -> Result<BetaScore<T>, BetaConfigError> {
+    config.validate()?;
+    let (endorse, report) = tally(ledger, s);
+    let evidence = config.evidence_weight;
+    let a = config.prior_endorse + evidence * cast::<T, f64>(endorse);
+    let b = config.prior_report + evidence * cast::<T, f64>(report);
+
+    let tail = (1. - config.credible_mass) / 2.;
+    let lower: T = cast(beta_quantile(tail, a, b));
+    let upper: T = cast(beta_quantile(1. - tail, a, b));
+    Ok(BetaScore {
+        mean: cast(a / (a + b)),
+        lower,
+        upper,
+        state: config.classify(lower, upper),
+    })
+}
+
+// ln Γ(x) for x > 0 - Lanczos approximation, g = 7
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1. - x);
+    }
+    let x = x - 1.;
+    let t = x + 7.5;
+    let mut sum = COEFFICIENTS[0];
+    for (i, c) in COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += c / (x + i as f64);
+    }
+    0.5 * (2. * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+// Continued fraction of the incomplete Beta function, evaluated with Lentz's method
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.;
+    let mut d = 1. - (a + b) * x / (a + 1.);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1. / d;
+    let mut h = d;
+    for m in 1..=300 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2. * m - 1.) * (a + 2. * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2. * m) * (a + 2. * m + 1.)),
+        ] {
+            d = 1. + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1. + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1. / d;
+            h *= d * c;
+        }
+        if (d * c - 1.).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+// Regularised incomplete Beta function I_x(a, b), the Beta(a, b) CDF
+fn beta_cdf(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+    let ln_front = ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln();
+    // The continued fraction converges fast on this side of the mean
+    if x < (a + 1.) / (a + b + 2.) {
+        ln_front.exp() * beta_continued_fraction(x, a, b) / a
+    } else {
+        1. - ln_front.exp() * beta_continued_fraction(1. - x, b, a) / b
+    }
+}
+
+// The CDF is monotone on [0, 1], so bisection always converges
+fn beta_quantile(p: f64, a: f64, b: f64) -> f64 {
+    let (mut low, mut high) = (0., 1.);
+    for _ in 0..QUANTILE_ITER {
+        let mid = (low + high) / 2.;
+        if beta_cdf(mid, a, b) < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.
+}
+
+// Calculate threshold
+pub fn calculate_snap_score_threshold<T: Scalar>(pre_trust: &[T]) -> T {
+    let non_zero = pre_trust
+        .iter()
+        .cloned()
+        .filter(|x| *x != T::zero())
+        .collect::<Vec<T>>();
+
+    let mut min = T::max_value();
+    non_zero.into_iter().for_each(|x| {
+        if x < min {
+            min = x;
+        }
+    });
+
+    min
+}
+
+// Attestations of every snap, keyed by snap id (usually the hash of the snap)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapRegistry {
+    snaps: BTreeMap<String, Ledger>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapScore<T = f32> {
+    pub id: String,
+    pub score: T,
+    pub confidence: T,
+    pub state: State,
+}
+
+impl SnapRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Record an attestation - the first one registers the snap
+    pub fn attest(&mut self, snap: &str, attestation: Attestation) -> Result<(), AttestationError> {
+        match self.snaps.get_mut(snap) {
+            Some(ledger) => ledger.add(attestation),
+            None => {
+                let ledger = Ledger::from_attestations(vec![attestation])?;
+                self.snaps.insert(snap.to_string(), ledger);
+                Ok(())
+            }
+        }
+    }
+
+    // Returns the ledger previously registered under `snap`, if any
+    pub fn insert(&mut self, snap: &str, ledger: Ledger) -> Option<Ledger> {
+        self.snaps.insert(snap.to_string(), ledger)
+    }
+
+    pub fn get(&self, snap: &str) -> Option<&Ledger> {
+        self.snaps.get(snap)
+    }
+
+    pub fn len(&self) -> usize {
+        self.snaps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snaps.is_empty()
+    }
+
+    // Snaps in id order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Ledger)> {
+        self.snaps.iter().map(|(id, ledger)| (id.as_str(), ledger))
+    }
+
+    // Score every snap against the adjusted peer scores of a domain, in id order. Snaps are
+    // independent, so parallel execution gives the same result.
+    pub fn score<T: Scalar>(
+        &self,
+        s: &[T],
+        thresholds: &Thresholds<T>,
+        execution: Execution,
+    ) -> Vec<SnapScore<T>> {
+        let score = |(id, ledger): (&String, &Ledger)| {
+            let (score, confidence, state) =
+                calculate_snap_score_with_threshold(ledger, s, thresholds);
+            SnapScore {
+                id: id.clone(),
+                score,
+                confidence,
+                state,
+            }
+        };
+        match execution {
+            Execution::Sequential => self.snaps.iter().map(score).collect(),
+            Execution::Parallel => self.snaps.par_iter().map(score).collect(),
+        }
+    }
+
+    // Beta posterior of every snap, in id order
+    pub fn score_beta<T: Scalar>(
+        &self,
+        s: &[T],
+        config: &BetaConfig,
+        execution: Execution,
+    ) -> Result<Vec<(String, BetaScore<T>)>, BetaConfigError> {
+        let score = |(id, ledger): (&String, &Ledger)| {
+            calculate_beta_snap_score(ledger, s, config).map(|score| (id.clone(), score))
+        };
+        match execution {
+            Execution::Sequential => self.snaps.iter().map(score).collect(),
+            Execution::Parallel => self.snaps.par_iter().map(score).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapOrder {
+    Id,
+    // Highest first, ties broken by id
+    Score,
+    Confidence,
+}
+
+pub fn sort_snaps<T: Scalar>(scores: &mut [SnapScore<T>], order: SnapOrder) {
+    let by_id = |a: &SnapScore<T>, b: &SnapScore<T>| a.id.cmp(&b.id);
+    let descending = |a: T, b: T| b.partial_cmp(&a).unwrap_or(Ordering::Equal);
+    match order {
+        SnapOrder::Id => scores.sort_by(by_id),
+        SnapOrder::Score => {
+            scores.sort_by(|a, b| descending(a.score, b.score).then_with(|| by_id(a, b)))
+        }
+        SnapOrder::Confidence => {
+            scores.sort_by(|a, b| descending(a.confidence, b.confidence).then_with(|| by_id(a, b)))
+        }
+    }
+}
+
+pub fn with_state<T>(scores: &[SnapScore<T>], state: State) -> impl Iterator<Item = &SnapScore<T>> {
+    scores.iter().filter(move |x| x.state == state)
+}
+
+// A snap whose verdict changed between two rounds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transition {
+    pub snap: String,
+    pub round: usize,
+    pub from: State,
+    pub to: State,
+}
+
+impl Transition {
+    // The snap was endorsed and no longer is
+    pub fn lost_endorsement(&self) -> bool {
+        self.from == State::Endorsed && self.to != State::Endorsed
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "round {}: {} {:?} -> {:?}",
+            self.round, self.snap, self.from, self.to
+        )
+    }
+}
+
+// State of every snap in every round it was scored. Rounds are numbered from 1.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnapHistory {
+    round: usize,
+    states: BTreeMap<String, Vec<(usize, State)>>,
+}
+
+impl SnapHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Record the scores of a new round and return the snaps whose state changed since the
+    // last round they were scored in. A snap seen for the first time has no transition.
+    pub fn record<T>(&mut self, scores: &[SnapScore<T>]) -> Vec<Transition> {
+        self.round += 1;
+        let mut transitions = Vec::new();
+        for snap in scores {
+            let states = self.states.entry(snap.id.clone()).or_default();
+            if let Some((_, from)) = states.last() {
+                if *from != snap.state {
+                    transitions.push(Transition {
+                        snap: snap.id.clone(),
+                        round: self.round,
+                        from: *from,
+                        to: snap.state,
+                    });
+                }
+            }
+            states.push((self.round, snap.state));
+        }
+        transitions
+    }
+
+    // Number of rounds recorded so far
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    // Latest state of a snap
+    pub fn state(&self, snap: &str) -> Option<State> {
+        self.states
+            .get(snap)
+            .and_then(|states| states.last())
+            .map(|(_, state)| *state)
+    }
+
+    // `(round, state)` for every round the snap was scored in
+    pub fn history(&self, snap: &str) -> Option<&[(usize, State)]> {
+        self.states.get(snap).map(|states| states.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(x: f64, y: f64) {
+        assert!((x - y).abs() < 1e-6, "{} != {}", x, y);
+    }
+
+    #[test]
+    fn ln_gamma_matches_factorials() {
+        assert_close(ln_gamma(1.), 0.);
+        assert_close(ln_gamma(2.), 0.);
+        assert_close(ln_gamma(5.), 24f64.ln());
+        assert_close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln());
+        assert_close(ln_gamma(0.25), 3.625_609_908_221_908f64.ln());
+    }
+
+    #[test]
+    fn beta_cdf_matches_closed_forms() {
+        for x in [0.1, 0.5, 0.9] {
+            // Beta(1, 1) is uniform, and Beta(2, 1) has CDF x^2
+            assert_close(beta_cdf(x, 1., 1.), x);
+            assert_close(beta_cdf(x, 2., 1.), x * x);
+            // Beta(2, 5) has CDF 1 - (1 - x)^5 (1 + 5x) on both sides of the continued fraction
+            assert_close(beta_cdf(x, 2., 5.), 1. - (1. - x).powi(5) * (1. + 5. * x));
+        }
+    }
+
+    #[test]
+    fn beta_quantiles_match_known_values() {
+        for p in [0.05, 0.5, 0.95] {
+            assert_close(beta_quantile(p, 1., 1.), p);
+        }
+        assert_close(beta_quantile(0.05, 2., 5.), 0.062_849_9);
+        assert_close(beta_quantile(0.5, 2., 5.), 0.264_450_0);
+        assert_close(beta_quantile(0.95, 2., 5.), 0.581_803_4);
+        // Symmetric around 1/2
+        assert_close(beta_quantile(0.5, 3., 3.), 0.5);
+        assert_close(
+            beta_quantile(0.05, 3., 3.),
+            1. - beta_quantile(0.95, 3., 3.),
+        );
+    }
+
+    #[test]
+    fn invalid_beta_config_is_an_error() {
+        let ledger = Ledger::default();
+        for config in [
+            BetaConfig {
+                prior_report: 0.,
+                ..BetaConfig::default()
+            },
+            BetaConfig {
+                evidence_weight: f64::NAN,
+                ..BetaConfig::default()
+            },
+            BetaConfig {
+                credible_mass: 1.,
+                ..BetaConfig::default()
+            },
+            BetaConfig {
+                decision: 1.2,
+                ..BetaConfig::default()
+            },
+            BetaConfig {
+                decision: f64::NAN,
+                ..BetaConfig::default()
+            },
+            BetaConfig {
+                max_width: -0.1,
+                ..BetaConfig::default()
+            },
+            BetaConfig {
+                max_width: f64::INFINITY,
+                ..BetaConfig::default()
+            },
+        ] {
+            assert!(config.validate().is_err());
+            assert!(calculate_beta_snap_score(&ledger, &[1f32], &config).is_err());
+        }
+        assert!(calculate_beta_snap_score(&ledger, &[1f32], &BetaConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn latest_vote_counts() {
+        let ledger = Ledger::from_attestations(vec![
+            Attestation::endorse(0, 1.).at(5),
+            Attestation::report(0, 1.).at(3),
+            Attestation::endorse(1, 1.),
+            Attestation::report(1, 0.5),
+            Attestation::report(2, 1.).at(7),
+            Attestation::endorse(2, 1.),
+        ])
+        .unwrap();
+        let votes = ledger.votes();
+        assert_eq!(votes.len(), 3);
+        // An older timestamp loses, without timestamps on both sides the last one wins
+        assert_eq!(votes[&0], Attestation::endorse(0, 1.).at(5));
+        assert_eq!(votes[&1], Attestation::report(1, 0.5));
+        assert_eq!(votes[&2], Attestation::endorse(2, 1.));
+        // Equal timestamps go to the later attestation
+        let ledger = Ledger::from_attestations(vec![
+            Attestation::endorse(0, 1.).at(5),
+            Attestation::report(0, 1.).at(5),
+        ])
+        .unwrap();
+        assert_eq!(ledger.votes()[&0].vote, Vote::Report);
+    }
+
+    #[test]
+    fn weights_outside_the_unit_interval_are_rejected() {
+        let mut ledger = Ledger::new();
+        for weight in [-0.1, 1.1, f32::NAN, f32::INFINITY] {
+            let error = ledger.add(Attestation::endorse(3, weight)).unwrap_err();
+            assert!(matches!(
+                error,
+                AttestationError::InvalidWeight { peer: 3, .. }
+            ));
+        }
+        assert!(ledger.is_empty());
+        assert!(ledger.add(Attestation::endorse(3, 0.)).is_ok());
+        assert!(ledger.add(Attestation::report(3, 1.)).is_ok());
+        assert!(Ledger::from_attestations(vec![Attestation::report(0, 2.)]).is_err());
+    }
+
+    #[test]
+    fn tally_skips_peers_without_positive_scores() {
+        let ledger = Ledger::from_attestations(vec![
+            Attestation::endorse(0, 1.),
+            Attestation::report(1, 1.),
+            Attestation::report(2, 1.),
+            Attestation::endorse(3, 0.5),
+            Attestation::report(9, 1.),
+        ])
+        .unwrap();
+        // Peer 1 is distrusted, peer 2 has no trust and peer 9 has no score
+        let s = [0.4f32, -0.2, 0., 0.6];
+        assert_eq!(tally(&ledger, &s), (0.4 + 0.3, 0.));
+        assert_eq!(calculate_snap_score(&ledger, &s), 1.);
+        assert_eq!(calculate_snap_score(&ledger, &[0f32; 4]), 0.);
+    }
+
+    fn snap_score(id: &str, score: f32, confidence: f32, state: State) -> SnapScore {
+        SnapScore {
+            id: id.to_string(),
+            score,
+            confidence,
+            state,
+        }
+    }
+
+    #[test]
+    fn registry_scores_every_snap_in_id_order() {
+        let mut registry = SnapRegistry::new();
+        registry.attest("c", Attestation::report(0, 1.)).unwrap();
+        registry.attest("a", Attestation::endorse(0, 1.)).unwrap();
+        registry.attest("a", Attestation::endorse(1, 1.)).unwrap();
+        registry.attest("b", Attestation::endorse(0, 1.)).unwrap();
+        registry.attest("b", Attestation::report(1, 1.)).unwrap();
+        registry.attest("d", Attestation::endorse(2, 1.)).unwrap();
+        let s = [0.5f32, 0.5, 0.];
+        let thresholds = Thresholds::symmetric(0.3);
+
+        let scores = registry.score(&s, &thresholds, Execution::Sequential);
+        assert_eq!(
+            scores,
+            vec![
+                snap_score("a", 1., 1., State::Endorsed),
+                snap_score("b", 0.5, 1., State::Contested),
+                snap_score("c", 0., 0.5, State::Reported),
+                snap_score("d", 0., 0., State::Unverified),
+            ]
+        );
+        assert_eq!(registry.score(&s, &thresholds, Execution::Parallel), scores);
+        let states = |state| {
+            with_state(&scores, state)
+                .map(|x| x.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(states(State::Endorsed), ["a"]);
+        assert_eq!(states(State::Unverified), ["d"]);
+        assert_eq!(states(State::Contested), ["b"]);
+        assert_eq!(states(State::Reported), ["c"]);
+    }
+
+    #[test]
+    fn ties_are_sorted_by_id() {
+        let mut scores = vec![
+            snap_score("d", 0.5, 0.2, State::Contested),
+            snap_score("b", 0.9, 0.2, State::Endorsed),
+            snap_score("c", 0.5, 0.4, State::Contested),
+            snap_score("a", 0.5, 0.2, State::Contested),
+        ];
+        let ids = |scores: &[SnapScore]| scores.iter().map(|x| x.id.clone()).collect::<Vec<_>>();
+        sort_snaps(&mut scores, SnapOrder::Score);
+        assert_eq!(ids(&scores), ["b", "a", "c", "d"]);
+        sort_snaps(&mut scores, SnapOrder::Confidence);
+        assert_eq!(ids(&scores), ["c", "a", "b", "d"]);
+        sort_snaps(&mut scores, SnapOrder::Id);
+        assert_eq!(ids(&scores), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn history_reports_changed_states() {
+        let mut history = SnapHistory::new();
+        let first = [
+            snap_score("a", 0.9, 1., State::Endorsed),
+            snap_score("b", 0.1, 1., State::Reported),
+        ];
+        assert!(history.record(&first).is_empty());
+        assert_eq!(history.round(), 1);
+
+        let second = [
+            snap_score("a", 0.5, 1., State::Contested),
+            snap_score("b", 0.1, 1., State::Reported),
+            snap_score("c", 0.9, 1., State::Endorsed),
+        ];
+        let transitions = history.record(&second);
+        assert_eq!(
+            transitions,
+            [Transition {
+                snap: "a".to_string(),
+                round: 2,
+                from: State::Endorsed,
+                to: State::Contested,
+            }]
+        );
+        assert!(transitions[0].lost_endorsement());
+        assert_eq!(history.state("a"), Some(State::Contested));
+        assert_eq!(
+            history.history("a").unwrap(),
+            [(1, State::Endorsed), (2, State::Contested)]
+        );
+        assert_eq!(history.history("c").unwrap(), [(2, State::Endorsed)]);
+        assert_eq!(history.state("d"), None);
+    }
+
+    #[test]
+    fn fixed_thresholds_are_checked() {
+        let thresholds = SnapConfig::default().thresholds::<f32>(&[]);
+        assert_eq!(
+            thresholds,
+            Thresholds {
+                confidence: 0.3,
+                report: 0.3,
+                endorse: 0.7,
+            }
+        );
+        for config in [
+            SnapConfig {
+                report_threshold: 0.8,
+                ..SnapConfig::default()
+            },
+            SnapConfig {
+                confidence_threshold: f64::NAN,
+                ..SnapConfig::default()
+            },
+            SnapConfig {
+                secure_threshold: 1.5,
+                ..SnapConfig::default()
+            },
+        ] {
+            assert!(config.validate().is_err(), "{:?}", config);
+        }
+        assert!(SnapConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn symmetric_thresholds_are_checked() {
+        let symmetric = |threshold| SnapConfig {
+            policy: ThresholdPolicy::Symmetric { threshold },
+            ..SnapConfig::default()
+        };
+        assert_eq!(
+            symmetric(0.25).thresholds::<f64>(&[]),
+            Thresholds {
+                confidence: 0.25,
+                report: 0.25,
+                endorse: 0.75,
+            }
+        );
+        assert!(symmetric(0.5).validate().is_ok());
+        for threshold in [0.6, -0.1, f64::NAN] {
+            assert!(symmetric(threshold).validate().is_err(), "{}", threshold);
+        }
+        // The fixed cut-offs are not used, so they are not checked
+        let config = SnapConfig {
+            report_threshold: 0.9,
+            ..symmetric(0.2)
+        };
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn min_pre_trust_thresholds_follow_the_pre_trust() {
+        let config = SnapConfig {
+            policy: ThresholdPolicy::MinPreTrust,
+            ..SnapConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            config.thresholds(&[0.5f32, 0., 0.25, 0.25]),
+            Thresholds::symmetric(0.25)
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+
+use rayon::prelude::*;
+
+use crate::linalg::{cast, Norm, Scalar, Vector};
+use crate::utils::Execution;
+
+// Compressed sparse row matrix - row `i` holds the outgoing weights of peer `i`
+#[derive(Debug, Clone)]
+pub struct CsrMatrix<T = f32> {
+    size: usize,
+    row_ptr: Vec<usize>,
+    col_idx: Vec<usize>,
+    values: Vec<T>,
+}
+
+impl<T: Scalar> CsrMatrix<T> {
+    // Build from coordinate (COO) entries - duplicates are summed, zeros are dropped.
+    // Every index must be below `size`, otherwise this panics - inputs from outside the crate
+    // are checked before they get here, see `update_run`.
+    pub fn from_triplets(size: usize, mut entries: Vec<(usize, usize, T)>) -> Self {
+        entries.sort_unstable_by_key(|e| (e.0, e.1));
+
+        let mut row_ptr = vec![0; size + 1];
+        let mut col_idx = Vec::with_capacity(entries.len());
+        let mut values: Vec<T> = Vec::with_capacity(entries.len());
+        let mut last: Option<(usize, usize)> = None;
+        for (i, j, v) in entries {
+            assert!(
+                i < size && j < size,
+                "entry ({}, {}) is outside a {} x {} matrix",
+                i,
+                j,
+                size,
+                size
+            );
+            if last == Some((i, j)) {
+                *values.last_mut().unwrap() += v;
+                continue;
+            }
+            last = Some((i, j));
+            row_ptr[i + 1] += 1;
+            col_idx.push(j);
+            values.push(v);
+        }
+        for i in 0..size {
+            row_ptr[i + 1] += row_ptr[i];
+        }
+
+        let matrix = Self {
+            size,
+            row_ptr,
+            col_idx,
+            values,
+        };
+        matrix.drop_zeros()
+    }
+
+    // `m` must be square - a longer row panics in `from_triplets`
+    pub fn from_dense(m: &[Vec<T>]) -> Self {
+        let mut entries = Vec::new();
+        for (i, row) in m.iter().enumerate() {
+            for (j, v) in row.iter().enumerate() {
+                if *v != T::zero() {
+                    entries.push((i, j, *v));
+                }
+            }
+        }
+        Self::from_triplets(m.len(), entries)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn row(&self, i: usize) -> impl Iterator<Item = (usize, T)> + '_ {
+        let range = self.row_ptr[i]..self.row_ptr[i + 1];
+        self.col_idx[range.clone()]
+            .iter()
+            .cloned()
+            .zip(self.values[range].iter().cloned())
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> T {
+        let range = self.row_ptr[i]..self.row_ptr[i + 1];
+        match self.col_idx[range.clone()].binary_search(&j) {
+            Ok(pos) => self.values[range.start + pos],
+            Err(_) => T::zero(),
+        }
+    }
+
+    // All non-zero entries as (from, to, value), in row order
+    pub fn entries(&self) -> impl Iterator<Item = (usize, usize, T)> + '_ {
+        (0..self.size).flat_map(move |i| self.row(i).map(move |(j, v)| (i, j, v)))
+    }
+
+    // Normalise every row to sum to 1 - rows summing to zero stay empty
+    pub fn normalise_rows(&self) -> Self {
+        let mut entries = Vec::with_capacity(self.nnz());
+        for i in 0..self.size {
+            let sum = Norm::L1.of(&self.values[self.row_ptr[i]..self.row_ptr[i + 1]]);
+            if sum == T::zero() {
+                continue;
+            }
+            for (j, v) in self.row(i) {
+                entries.push((i, j, v / sum));
+            }
+        }
+        Self::from_triplets(self.size, entries)
+    }
+
+    // Rows without any weight, e.g. peers who trust nobody
+    pub fn empty_rows(&self) -> Vec<usize> {
+        (0..self.size)
+            .filter(|i| {
+                Norm::L1.of(&self.values[self.row_ptr[*i]..self.row_ptr[*i + 1]]) == T::zero()
+            })
+            .collect()
+    }
+
+    // Copy with the given rows replaced by new (column, value) entries - other rows are kept
+    pub fn replace_rows(&self, rows: &[(usize, Vec<(usize, T)>)]) -> Self {
+        let replaced: HashSet<usize> = rows.iter().map(|(i, _)| *i).collect();
+        let mut entries: Vec<(usize, usize, T)> = self
+            .entries()
+            .filter(|(i, _, _)| !replaced.contains(i))
+            .collect();
+        for (i, row) in rows {
+            entries.extend(row.iter().map(|(j, v)| (*i, *j, *v)));
+        }
+        Self::from_triplets(self.size, entries)
+    }
+
+    // Transposed copy - the CSC view of the original, where row `i` holds the incoming weights
+    pub fn transpose(&self) -> Self {
+        let entries = self.entries().map(|(i, j, v)| (j, i, v)).collect();
+        Self::from_triplets(self.size, entries)
+    }
+
+    // Same matrix in another precision
+    pub fn cast<U: Scalar>(&self) -> CsrMatrix<U> {
+        CsrMatrix {
+            size: self.size,
+            row_ptr: self.row_ptr.clone(),
+            col_idx: self.col_idx.clone(),
+            values: self.values.iter().map(|v| cast(*v)).collect(),
+        }
+    }
+
+    // Matrix-vector product: out[i] = sum_j m[i][j] * s[j]
+    pub fn mul_vec(&self, s: &[T]) -> Vector<T> {
+        let mut out = vec![T::zero(); self.size];
+        for (i, out) in out.iter_mut().enumerate() {
+            for (j, v) in self.row(i) {
+                *out += v * s[j];
+            }
+        }
+        out
+    }
+
+    // A single entry of the product, summed in the same order as `mul_vec`
+    pub fn row_dot(&self, i: usize, s: &[T]) -> T {
+        self.row(i).fold(T::zero(), |acc, (j, v)| acc + v * s[j])
+    }
+
+    // Same product with the rows split across the rayon thread pool
+    pub fn par_mul_vec(&self, s: &[T]) -> Vector<T> {
+        (0..self.size)
+            .into_par_iter()
+            .map(|i| self.row_dot(i, s))
+            .collect()
+    }
+
+    pub fn mul_vec_with(&self, s: &[T], execution: Execution) -> Vector<T> {
+        match execution {
+            Execution::Sequential => self.mul_vec(s),
+            Execution::Parallel => self.par_mul_vec(s),
+        }
+    }
+
+    fn drop_zeros(mut self) -> Self {
+        if self.values.iter().all(|v| *v != T::zero()) {
+            return self;
+        }
+        let mut row_ptr = vec![0; self.size + 1];
+        let mut col_idx = Vec::with_capacity(self.nnz());
+        let mut values = Vec::with_capacity(self.nnz());
+        for i in 0..self.size {
+            for (j, v) in self.row(i) {
+                if v != T::zero() {
+                    col_idx.push(j);
+                    values.push(v);
+                }
+            }
+            row_ptr[i + 1] = values.len();
+        }
+        self.row_ptr = row_ptr;
+        self.col_idx = col_idx;
+        self.values = values;
+        self
+    }
+}
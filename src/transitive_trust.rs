@@ -5,13 +5,13 @@ use std::{
 };
 
 #[derive(Debug, Clone)]
-struct Result {
-    node: String,
-    p_score: f32,
-    n_score: f32,
+pub struct NodeScore {
+    pub node: String,
+    pub p_score: f32,
+    pub n_score: f32,
 }
 
-impl Result {
+impl NodeScore {
     pub fn new(node: String, p_score: f32, n_score: f32) -> Self {
         Self {
             node,
@@ -25,8 +25,8 @@ impl Result {
     }
 }
 
-#[derive(Debug, Clone)]
-struct Node {
+#[derive(Debug, Clone, Default)]
+pub struct Node {
     positive_edges: HashMap<String, f32>,
     negative_edges: HashMap<String, f32>,
 }
@@ -63,8 +63,8 @@ impl Node {
     }
 }
 
-#[derive(Debug, Clone)]
-struct Graph {
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
     nodes: HashMap<String, Node>,
 }
 
@@ -120,7 +120,7 @@ impl Graph {
     }
 }
 
-pub fn compute_scores(graph: Graph, source: String) -> Vec<Result> {
+pub fn compute_scores(graph: Graph, source: String) -> Vec<NodeScore> {
     let mut p_scores = HashMap::<String, f32>::new();
     let mut n_scores = HashMap::<String, f32>::new();
     let mut inspected = HashSet::<String>::new();
@@ -183,7 +183,7 @@ pub fn compute_scores(graph: Graph, source: String) -> Vec<Result> {
         if node != source {
             let p_score = p_scores.get(&node).unwrap();
             let n_score = n_scores.get(&node).unwrap();
-            let result = Result::new(node, *p_score, *n_score);
+            let result = NodeScore::new(node, *p_score, *n_score);
             results.push(result);
         }
     }
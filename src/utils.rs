@@ -1,44 +1,153 @@
-pub fn transpose<const N: usize>(
-    s: [[f32; N]; N],
-) -> [[f32; N]; N] {
-    let mut new_s: [[f32; N]; N] = [[0.; N]; N];
-    for i in 0..N {
-        for j in 0..N {
-            new_s[i][j] = s[j][i];
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::linalg::{cast, vec_sub, Norm, Scalar};
+
+pub fn format_vec<T: fmt::Display>(s: &[T]) -> String {
+    s.iter()
+        .map(|v| format!("{:>9.4}", v))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+// Distance between successive score vectors used to detect convergence
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Residual {
+    L1,
+    LInf,
+}
+
+impl Residual {
+    pub fn distance<T: Scalar>(&self, s: &[T], y: &[T]) -> T {
+        let diff = vec_sub(s, y);
+        match self {
+            Residual::L1 => Norm::L1.of(&diff),
+            Residual::LInf => Norm::Max.of(&diff),
         }
     }
-    new_s
 }
 
-pub fn normalise<const N: usize>(
-    am_vec: [f32; N],
-    seed: [f32; N],
-) -> [f32; N] {
-    let sum: f32 = am_vec.iter().sum();
-    if sum == 0. {
-        return seed;
+// How the matrix-vector product of each iteration is computed. Parallel partitions the rows
+// across threads - every row is still summed in the same order, so results are bit-identical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Execution {
+    #[default]
+    Sequential,
+    Parallel,
+}
+
+// Stop once the residual drops to `tolerance`, or after `max_iter` iterations
+#[derive(Debug, Clone, Copy)]
+pub struct Convergence {
+    pub tolerance: f64,
+    pub max_iter: usize,
+    pub residual: Residual,
+}
+
+impl Convergence {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.tolerance >= 0. && self.tolerance.is_finite()) {
+            return Err(format!(
+                "tolerance must be a non-negative number, found {}",
+                self.tolerance
+            ));
+        }
+        Ok(())
     }
-    am_vec.map(|x| x / sum)
+
+    // The residual is compared in the precision of the scores, and reported as f32
+    pub fn check<T: Scalar>(&self, iterations: usize, prev: &[T], next: &[T]) -> ConvergenceReport {
+        let residual = self.residual.distance(prev, next);
+        ConvergenceReport {
+            iterations,
+            residual: cast(residual),
+            converged: residual <= cast(self.tolerance),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConvergenceReport {
+    pub iterations: usize,
+    pub residual: f32,
+    pub converged: bool,
 }
 
-pub fn normalise_sqrt<const N: usize>(
-    vector: [f32; N],
-) -> [f32; N] {
-    let sum: f32 = vector.iter().map(|x| x.powf(2.)).sum();
-    if sum == 0. {
-        return [0.; N];
+impl Default for ConvergenceReport {
+    fn default() -> Self {
+        Self {
+            iterations: 0,
+            residual: f32::INFINITY,
+            converged: false,
+        }
     }
-    vector.map(|x| x / sum.sqrt())
 }
 
-pub fn vec_scalar_mul<const N: usize>(s: [f32; N], y: f32) -> [f32; N] {
-    s.map(|x| x * y)
+impl std::fmt::Display for ConvergenceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "iterations: {}, residual: {:e}, converged: {}",
+            self.iterations, self.residual, self.converged
+        )
+    }
 }
 
-pub fn vec_add<const N: usize>(s: [f32; N], y: [f32; N]) -> [f32; N] {
-    let mut out: [f32; N] = [0.; N];
-    for i in 0..N {
-        out[i] = s[i] + y[i];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eigen_trust::{self, EigenTrustConfig};
+    use crate::page_rank::{self, PageRankConfig};
+    use crate::sparse::CsrMatrix;
+
+    // A few thousand edges over enough peers for rayon to split the rows across threads
+    fn graph(size: usize) -> CsrMatrix<f64> {
+        let mut state: u64 = 7;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            (state >> 33) as usize
+        };
+        let mut entries = Vec::new();
+        for i in 0..size {
+            for _ in 0..8 {
+                let j = next() % size;
+                if i != j {
+                    entries.push((i, j, (next() % 100) as f64 / 10.));
+                }
+            }
+        }
+        CsrMatrix::from_triplets(size, entries)
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let size = 2000;
+        let lt = graph(size);
+        let ld = CsrMatrix::from_triplets(size, vec![]);
+        let pre_trust = vec![1. / size as f64; size];
+
+        let config = EigenTrustConfig::default();
+        let sequential = eigen_trust::run(&lt, &ld, &pre_trust, &config).unwrap();
+        let config = EigenTrustConfig {
+            execution: Execution::Parallel,
+            ..config
+        };
+        let parallel = eigen_trust::run(&lt, &ld, &pre_trust, &config).unwrap();
+        assert_eq!(sequential.trust, parallel.trust);
+        assert_eq!(sequential.adjusted, parallel.adjusted);
+
+        let config = PageRankConfig::default();
+        let (sequential, _) = page_rank::run(&lt, &pre_trust, &pre_trust, &config).unwrap();
+        let config = PageRankConfig {
+            execution: Execution::Parallel,
+            ..config
+        };
+        let (parallel, _) = page_rank::run(&lt, &pre_trust, &pre_trust, &config).unwrap();
+        assert_eq!(sequential, parallel);
     }
-    out
 }